@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Achievement {
+    MinimumMoves,
+    TenLevels,
+    SixCube,
+    NoUndo,
+}
+
+impl Achievement {
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::MinimumMoves => "Clear a level in the minimum number of moves",
+            Self::TenLevels => "Clear 10 levels",
+            Self::SixCube => "Clear a 6-cube",
+            Self::NoUndo => "Clear a level without undoing",
+        }
+    }
+}
+
+// unlocked once and kept for good, stored with the save data
+#[derive(Resource, Default)]
+pub struct Achievements(pub HashSet<Achievement>);
+
+// whether undo was used on this attempt at the level
+#[derive(Resource, Default)]
+pub struct UsedUndo(pub bool);
+
+// what a cleared level looked like, as far as achievements care
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionSummary {
+    pub moves: u32,
+    // fewest moves that clear the level, if the solver could work it out
+    pub optimal: Option<u32>,
+    pub used_undo: bool,
+    // smallest extent of the level's bounds, so a 6x6x6 level counts but a 6x6x2 slab doesn't
+    pub side_len: i32,
+    // distinct levels cleared so far, including this one
+    pub levels_cleared: usize,
+}
+
+// the side_len of a level within these bounds
+pub fn side_len(lower: Vec3, upper: Vec3) -> i32 {
+    (upper - lower).min_element() as i32
+}
+
+pub fn earned(summary: &CompletionSummary) -> Vec<Achievement> {
+    let mut earned = Vec::new();
+    if summary.optimal.is_some_and(|optimal| summary.moves <= optimal) {
+        earned.push(Achievement::MinimumMoves);
+    }
+    if summary.levels_cleared >= 10 {
+        earned.push(Achievement::TenLevels);
+    }
+    if summary.side_len >= 6 {
+        earned.push(Achievement::SixCube);
+    }
+    if !summary.used_undo {
+        earned.push(Achievement::NoUndo);
+    }
+    earned
+}
+
+// the ones earned by this completion that weren't unlocked before
+pub fn unlock(achievements: &mut Achievements, summary: &CompletionSummary) -> Vec<Achievement> {
+    earned(summary).into_iter().filter(|a| achievements.0.insert(*a)).collect()
+}
+
+const TOAST_SECS: f32 = 3.0;
+
+#[derive(Component)]
+pub struct Toast(Timer);
+
+// not tied to a screen, so it stays up across the switch back to the menu
+pub fn show_toast(commands: &mut Commands, achievement: Achievement, row: usize) {
+    commands.spawn((
+        Text::new(format!("Achievement unlocked: {}", achievement.name())),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.1)),
+        TextShadow::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.0 + 34.0 * row as f32),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        GlobalZIndex(1),
+        Toast(Timer::from_seconds(TOAST_SECS, TimerMode::Once)),
+    ));
+}
+
+pub fn expire_toasts(
+    mut commands: Commands,
+    mut toasts: Query<(Entity, &mut Toast, &mut TextColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut toast, mut color) in toasts.iter_mut() {
+        toast.0.tick(time.delta());
+        if toast.0.finished() {
+            commands.entity(entity).despawn();
+        } else {
+            color.0.set_alpha(1.0 - toast.0.fraction().powi(4));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a completion that earns nothing
+    fn plain() -> CompletionSummary {
+        CompletionSummary { moves: 9, optimal: Some(7), used_undo: true, side_len: 4, levels_cleared: 3 }
+    }
+
+    #[test]
+    fn nothing_for_a_plain_clear() {
+        assert_eq!(earned(&plain()), vec![]);
+    }
+
+    #[test]
+    fn minimum_moves() {
+        assert_eq!(earned(&CompletionSummary { moves: 7, ..plain() }), vec![Achievement::MinimumMoves]);
+        assert_eq!(earned(&CompletionSummary { moves: 8, ..plain() }), vec![]);
+        // without a solution there's no minimum to meet
+        assert_eq!(earned(&CompletionSummary { moves: 0, optimal: None, ..plain() }), vec![]);
+    }
+
+    #[test]
+    fn ten_levels() {
+        assert_eq!(earned(&CompletionSummary { levels_cleared: 9, ..plain() }), vec![]);
+        assert_eq!(earned(&CompletionSummary { levels_cleared: 10, ..plain() }), vec![Achievement::TenLevels]);
+    }
+
+    #[test]
+    fn six_cube() {
+        let slab = side_len(Vec3::ZERO, Vec3::new(6.0, 6.0, 2.0));
+        assert_eq!(earned(&CompletionSummary { side_len: slab, ..plain() }), vec![]);
+        let cube = side_len(Vec3::splat(-3.0), Vec3::splat(3.0));
+        assert_eq!(earned(&CompletionSummary { side_len: cube, ..plain() }), vec![Achievement::SixCube]);
+    }
+
+    #[test]
+    fn no_undo() {
+        assert_eq!(earned(&CompletionSummary { used_undo: false, ..plain() }), vec![Achievement::NoUndo]);
+    }
+
+    #[test]
+    fn unlock_only_returns_new_ones() {
+        let mut achievements = Achievements(HashSet::from([Achievement::NoUndo]));
+        let summary = CompletionSummary { moves: 7, used_undo: false, ..plain() };
+        assert_eq!(unlock(&mut achievements, &summary), vec![Achievement::MinimumMoves]);
+        assert_eq!(achievements.0, HashSet::from([Achievement::MinimumMoves, Achievement::NoUndo]));
+        assert_eq!(unlock(&mut achievements, &summary), vec![]);
+    }
+}
@@ -0,0 +1,58 @@
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use crate::settings::Settings;
+
+#[derive(Resource)]
+pub struct SoundAssets {
+    pub slide: Handle<AudioSource>,
+    pub whoosh: Handle<AudioSource>,
+}
+
+pub fn load_sounds(asset_server: &AssetServer) -> SoundAssets {
+    SoundAssets {
+        slide: asset_server.load("sounds/slide.wav"),
+        whoosh: asset_server.load("sounds/whoosh.wav"),
+    }
+}
+
+pub fn play_sfx(commands: &mut Commands, clip: &Handle<AudioSource>, settings: &Settings) {
+    if settings.sfx_volume <= 0.0 {
+        return;
+    }
+    commands.spawn((
+        AudioPlayer::new(clip.clone()),
+        PlaybackSettings::DESPAWN.with_volume(Volume::Linear(settings.sfx_volume)),
+    ));
+}
+
+#[derive(Component)]
+pub struct MusicController;
+
+pub fn spawn_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    commands.spawn((
+        AudioPlayer::new(asset_server.load::<AudioSource>("sounds/music.wav")),
+        PlaybackSettings::LOOP.with_volume(Volume::Linear(settings.effective_music_volume())),
+        MusicController,
+    ));
+}
+
+pub fn apply_music_volume(
+    settings: Res<Settings>,
+    mut music_query: Query<&mut AudioSink, With<MusicController>>,
+) {
+    for mut sink in music_query.iter_mut() {
+        sink.set_volume(Volume::Linear(settings.effective_music_volume()));
+    }
+}
+
+pub fn pause_music(music_query: Query<&AudioSink, With<MusicController>>) {
+    music_query.iter().for_each(|sink| sink.pause());
+}
+
+pub fn resume_music(music_query: Query<&AudioSink, With<MusicController>>) {
+    music_query.iter().for_each(|sink| sink.play());
+}
@@ -1,8 +1,13 @@
-use bevy::math::*;
-use bevy::prelude::{Component, Reflect};
+use glam::{IVec2, IVec3, Quat, Vec3};
+#[cfg(feature = "engine")]
+use bevy::prelude::{Component, Reflect, Resource};
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Component, Reflect)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "engine", derive(Component, Reflect))]
 pub enum Axis { X, Y, Z }
 
 impl Axis {
@@ -12,6 +17,18 @@ impl Axis {
     pub const NOY: [Self; 2] = [Self::Z, Self::X];
     pub const NOZ: [Self; 2] = [Self::X, Self::Y];
 
+    pub fn from_index(i: usize) -> Option<Self> {
+        Self::ALL.get(i).copied()
+    }
+
+    pub fn to_index(self: &Self) -> usize {
+        match self {
+            Self::X => 0,
+            Self::Y => 1,
+            Self::Z => 2,
+        }
+    }
+
     pub fn next_rh(self: &Self) -> Self {
         match self {
             Self::X => Self::Y,
@@ -80,9 +97,20 @@ impl Axis {
             Self::Z => IVec3 { z: new_value, ..*v },
         }
     }
+
+    // quarter turn around this axis through the origin, counterclockwise looking down from its
+    // positive end: the first remaining axis turns into the second one
+    pub fn rotate_ivec3(self: &Self, v: IVec3) -> IVec3 {
+        let [u, w] = self.remaining_two();
+        let rotated = u.set_ivec3_component(&v, -w.ivec3_component(v));
+        w.set_ivec3_component(&rotated, u.ivec3_component(v))
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Component, Reflect)]
+// written as "XP", "ZN" and so on, like the consts below. The long form
+// { "axis": "X", "positive": true } of older files is still read
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "engine", derive(Component, Reflect))]
 pub struct Direction {
     pub axis: Axis,
     pub positive: bool,
@@ -100,6 +128,23 @@ impl Direction {
     pub const ZP: Self = Self { axis: Axis::Z, positive: true };
     pub const ZN: Self = Self { axis: Axis::Z, positive: false };
 
+    pub const ALL: [Self; 6] = [Self::XP, Self::XN, Self::YP, Self::YN, Self::ZP, Self::ZN];
+
+    const NAMES: [&str; 6] = ["XP", "XN", "YP", "YN", "ZP", "ZN"];
+
+    pub fn name(self: &Self) -> &'static str {
+        let i = Self::ALL.iter().position(|d| d == self).expect("every direction is in ALL");
+        Self::NAMES[i]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES.iter().position(|n| *n == name).map(|i| Self::ALL[i])
+    }
+
+    pub fn all() -> impl Iterator<Item=Self> {
+        Self::ALL.into_iter()
+    }
+
     pub fn sign(self: &Self) -> i32 {
         if self.positive { 1 } else { -1 }
     }
@@ -107,32 +152,110 @@ impl Direction {
     pub fn unit_vector(self: &Self) -> Vec3 {
         (self.sign() as f32) * self.axis.unit_vector()
     }
+
+    pub fn opposite(self: &Self) -> Self {
+        Self { axis: self.axis, positive: !self.positive }
+    }
+
+    // along the same axis but the other way, so two such blocks on a line head into each other
+    pub fn is_opposite(self: &Self, other: &Self) -> bool {
+        *other == self.opposite()
+    }
+
+    // same quarter turn as Axis::rotate_ivec3
+    pub fn rotate_around(self: &Self, axis: Axis) -> Self {
+        let [u, w] = axis.remaining_two();
+        match self.axis {
+            a if a == u => Self::new(w, self.positive),
+            a if a == w => Self::new(u, !self.positive),
+            _ => *self,
+        }
+    }
+
+    pub fn mirror(self: &Self, axis: Axis) -> Self {
+        Self { axis: self.axis, positive: self.positive != (self.axis == axis) }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DirectionRepr {
+    Compact(String),
+    Full { axis: Axis, positive: bool },
+}
+
+impl Serialize for Direction {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
 }
 
-fn check_overlap_rectangles(rect1: IRect, rect2: IRect) -> bool {
-    !rect1.intersect(rect2).is_empty()
+impl<'de> Deserialize<'de> for Direction {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match DirectionRepr::deserialize(deserializer)? {
+            DirectionRepr::Compact(name) => Self::from_name(&name)
+                .ok_or_else(|| serde::de::Error::custom(format!("unknown direction {:?}, expected one of {:?}", name, Self::NAMES))),
+            DirectionRepr::Full { axis, positive } => Ok(Self::new(axis, positive)),
+        }
+    }
 }
 
+// the model rotations, here rather than next to the models so they build and can be checked
+// without the engine
+pub fn rotate_axis_to_axis(ax_from: &Axis, ax_to: &Axis) -> Quat {
+    match ax_from.remaining(ax_to) {
+        None => Quat::IDENTITY,
+        Some(axis_to_rotate_around) => {
+            let angle = (std::f32::consts::PI / 2.0) * (ax_from.cross(ax_to) as f32);
+            Quat::from_axis_angle(
+                axis_to_rotate_around.unit_vector(),
+                angle
+            )
+        }
+    }
+}
+
+pub fn flip_if_necessary(dir: &Direction, ax: &Axis) -> Quat {
+    if dir.positive {
+        Quat::IDENTITY
+    }
+    else {
+        Quat::from_axis_angle(
+            ax.unit_vector(), std::f32::consts::PI
+        )
+    }
+}
+
+// whether the cross-sections of the two blocks perpendicular to `direction` share any area
 fn check_overlap_in_direction(b1: &Block, b2: &Block, direction: &Direction) -> bool {
-    let (rect1, rect2) = match direction.axis {
-        Axis::X =>
-            (IRect::new(b1.min.y, b1.min.z, b1.max.y, b1.max.z), IRect::new(b2.min.y, b2.min.z, b2.max.y, b2.max.z)),
-        Axis::Y =>
-            (IRect::new(b1.min.x, b1.min.z, b1.max.x, b1.max.z), IRect::new(b2.min.x, b2.min.z, b2.max.x, b2.max.z)),
-        Axis::Z =>
-            (IRect::new(b1.min.x, b1.min.y, b1.max.x, b1.max.y), IRect::new(b2.min.x, b2.min.y, b2.max.x, b2.max.y)),
-    };
-    check_overlap_rectangles(rect1, rect2)
+    direction.axis.remaining_two().iter().all(|ax| {
+        ax.ivec3_component(b1.min) < ax.ivec3_component(b2.max) && ax.ivec3_component(b2.min) < ax.ivec3_component(b1.max)
+    })
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Component, Reflect, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "engine", derive(Component, Reflect))]
 pub struct Block {
     pub direction: Direction,
     pub min: IVec3,
     pub max: IVec3,
+    // cells covered per leap: a sliding block only stops on multiples of it from where it
+    // started. Level files from before it existed load with the usual single step
+    #[serde(default = "Block::default_step")]
+    pub step: i32,
 }
 
 impl Block {
+    pub const DEFAULT_STEP: i32 = 1;
+
+    fn default_step() -> i32 {
+        Self::DEFAULT_STEP
+    }
+
+    pub fn new(direction: Direction, min: IVec3, max: IVec3) -> Self {
+        Self { direction, min, max, step: Self::DEFAULT_STEP }
+    }
+
     pub fn get_isize(self: &Self) -> IVec3 {
         self.max - self.min
     }
@@ -145,34 +268,97 @@ impl Block {
         self.max.as_vec3().midpoint(self.min.as_vec3())
     }
 
-    pub fn from_center_size(direction: Direction, center: Vec3, size: Vec3) -> Self {
+    // corners are rounded half-up on every axis (as_ivec3 would truncate toward zero, which
+    // shifts blocks with negative centers); None if the block would end up with no extent
+    pub fn from_center_size(direction: Direction, center: Vec3, size: Vec3) -> Option<Self> {
         let half_size = size * 0.5;
-        let min = (center - half_size).as_ivec3();
-        let max = (center + half_size).as_ivec3();
-        Block { direction, min, max }
+        let min = (center - half_size + 0.5).floor().as_ivec3();
+        let max = (center + half_size + 0.5).floor().as_ivec3();
+        (max - min).cmpgt(IVec3::ZERO).all().then_some(Block::new(direction, min, max))
     }
 
-    pub fn get_elongation(self: &Self) -> Option<Axis> {
+    pub const MAX_LENGTH: i32 = 3;
+
+    pub fn get_elongation(self: &Self) -> Option<(Axis, i32)> {
+       let long = |n: i32| (2..=Self::MAX_LENGTH).contains(&n);
        match self.get_isize() {
-           IVec3 { x: 1, y: 1, z: 1 } => None,
-           IVec3 { x: 2, y: 1, z: 1 } => Some(Axis::X),
-           IVec3 { x: 1, y: 2, z: 1 } => Some(Axis::Y),
-           IVec3 { x: 1, y: 1, z: 2 } => Some(Axis::Z),
-           _                          => None,
+           IVec3 { x: 1, y: 1, z: 1 }         => None,
+           IVec3 { x, y: 1, z: 1 } if long(x) => Some((Axis::X, x)),
+           IVec3 { x: 1, y, z: 1 } if long(y) => Some((Axis::Y, y)),
+           IVec3 { x: 1, y: 1, z } if long(z) => Some((Axis::Z, z)),
+           _                                  => None,
        }
     }
 
+    pub fn contains_point(self: &Self, p: IVec3) -> bool {
+        p.cmpge(self.min).all() && p.cmplt(self.max).all()
+    }
+
+    pub fn overlaps(self: &Self, other: &Self) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    // the corners swap places along the negated axis, so the new min/max are taken per component
+    pub fn rotate_around(self: &Self, axis: Axis) -> Self {
+        let (a, b) = (axis.rotate_ivec3(self.min), axis.rotate_ivec3(self.max));
+        Block { direction: self.direction.rotate_around(axis), min: a.min(b), max: a.max(b), ..*self }
+    }
+
+    // cells between the face the block leaves through and the matching side of the bounds
+    pub fn distance_to_exit(self: &Self, bound_min: IVec3, bound_max: IVec3) -> i32 {
+        let axis = self.direction.axis;
+        if self.direction.positive {
+            axis.ivec3_component(bound_max) - axis.ivec3_component(self.max)
+        } else {
+            axis.ivec3_component(self.min) - axis.ivec3_component(bound_min)
+        }
+    }
+
+    // reflection through the plane where `axis` is zero
+    pub fn mirror(self: &Self, axis: Axis) -> Self {
+        Block {
+            direction: self.direction.mirror(axis),
+            min: axis.set_ivec3_component(&self.min, -axis.ivec3_component(self.max)),
+            max: axis.set_ivec3_component(&self.max, -axis.ivec3_component(self.min)),
+            ..*self
+        }
+    }
+
+    // identity of a block that sorts and hashes, with the coordinates kept whole so blocks far
+    // apart never share a key
+    pub fn canonical_key(self: &Self) -> (i32, i32, i32, i32, i32, i32, u8, i32) {
+        let direction = self.direction.axis.to_index() as u8 * 2 + self.direction.positive as u8;
+        (
+            self.min.x, self.min.y, self.min.z,
+            self.max.x, self.max.y, self.max.z,
+            direction, self.step,
+        )
+    }
+
+    // the part of a slide over `distance` cells that a block leaping `step` cells at a time covers
+    pub fn stepped_distance(self: &Self, distance: i32) -> i32 {
+        distance - distance.rem_euclid(self.step.max(1))
+    }
+
     pub fn extract_mm(self: Block) -> (IVec3, IVec3) {
         (self.min, self.max)
     }
 
+    // cells between this block's leading face and the near face of `b`, negative if `b`
+    // reaches back past the leading face
+    fn gap_ahead(self: &Self, b: &Self) -> i32 {
+        let axis = self.direction.axis;
+        if self.direction.positive {
+            axis.ivec3_component(b.min) - axis.ivec3_component(self.max)
+        } else {
+            axis.ivec3_component(self.min) - axis.ivec3_component(b.max)
+        }
+    }
+
+    // faces rather than centers: wide blocks only partly in the way still count, and a block
+    // is only ahead if all of it is, so move_block can always stop in front of it
     fn possible_collision(self: &Self, b: &Self) -> bool {
-        let not_self = b != self;
-        let diff = b.get_center() - self.get_center();
-        let ahead = self.direction.unit_vector().dot(diff) >= 1.0;
-        let in_the_way = self.direction.axis.remaining_two().iter()
-            .all(|ax: &Axis| ax.vec3_component(diff).abs() < 1.0);
-        not_self && ahead && in_the_way
+        b != self && self.gap_ahead(b) >= 0 && check_overlap_in_direction(self, b, &self.direction)
     }
 
     pub fn get_blocks_in_front<I>(self: &Self, all_blocks: I) -> Vec<Self>
@@ -191,21 +377,78 @@ impl Block {
     {
         let res = all_blocks
             .filter(|b| self.possible_collision(b))
-            .min_by_key(|b: &Self| self.direction.unit_vector().dot(b.get_center() - self.get_center()) as i32);
+            .min_by_key(|b| self.gap_ahead(b));
         res
     }
 
+    // what a block leaving the level has to get past along its direction: the level's bound, or
+    // the far face of any block still ahead of it on its line, like one that is flying away itself
+    pub fn exit_edge<I>(self: &Self, present: I, bound: i32) -> i32
+    where
+        I: Iterator<Item=Self>
+    {
+        let Direction { axis, positive } = self.direction;
+        let ahead = present.filter(|b| b != self && check_overlap_in_direction(self, b, &self.direction));
+        if positive {
+            let front = axis.ivec3_component(self.max);
+            ahead.map(|b| axis.ivec3_component(b.max)).filter(|far| *far > front).fold(bound, i32::max)
+        } else {
+            let front = axis.ivec3_component(self.min);
+            ahead.map(|b| axis.ivec3_component(b.min)).filter(|far| *far < front).fold(bound, i32::min)
+        }
+    }
+
+    // `board` is every settled block. The ones ahead of this block on its line are pushed along
+    // with it, each as far as the bound or the nearest block in its way allows, in whole steps
+    // of its own. Blocks off the line that a wide pushed block runs into aren't pushed, they stop it
+    pub fn push_line(self: &Self, board: &[Self], bound: i32) -> Option<Vec<(Self, Self)>> {
+        let Direction { axis, positive } = self.direction;
+        let mut line: Vec<Self> = self.get_blocks_in_front(board.iter().copied());
+        // the farthest block moves first, so whatever is ahead of a block is already in place
+        line.sort_by_key(|b| std::cmp::Reverse(self.gap_ahead(b)));
+        let mut placed: Vec<Self> = board.iter()
+            .filter(|b| *b != self && !line.contains(b))
+            .copied()
+            .collect();
+        let mut moves: Vec<(Self, Self)> = Vec::new();
+        for b in line.iter().chain(std::iter::once(self)) {
+            // the pushed block travels the way this one does, whatever its own direction
+            let mover = Self { direction: self.direction, ..*b };
+            let to_bound = if positive { bound - axis.ivec3_component(b.max) } else { axis.ivec3_component(b.min) - bound };
+            let room = mover.get_nearest_block_in_front(placed.iter().copied())
+                .map_or(to_bound, |obstacle| mover.gap_ahead(&obstacle).min(to_bound));
+            let shift = axis.set_ivec3_component(&IVec3::ZERO, self.direction.sign() * b.stepped_distance(room.max(0)));
+            let new_block = Self { min: b.min + shift, max: b.max + shift, ..*b };
+            placed.push(new_block);
+            moves.push((*b, new_block));
+        }
+        match moves.last() {
+            Some((old, new)) if old != new => {
+                moves.retain(|(old, new)| old != new);
+                Some(moves)
+            },
+            _ => None,
+        }
+    }
+
     pub fn move_block(self: &Self, static_block: &Self) -> Option<Self> {
         if check_overlap_in_direction(self, static_block, &self.direction) {
-            let length = if self.get_elongation() == Some(self.direction.axis) { 2 } else { 1 };
+            let length = match self.get_elongation() {
+                Some((axis, length)) if axis == self.direction.axis => length,
+                _ => 1,
+            };
 
             let Direction { axis, positive } = self.direction;
+            // a block leaping several cells at a time stops short of the gap it can't fill
+            let shortfall = |gap: i32| gap - self.stepped_distance(gap);
             match positive {
                 true  =>
                     if axis.ivec3_component(self.max) <= axis.ivec3_component(static_block.min) {
+                        let gap = axis.ivec3_component(static_block.min) - axis.ivec3_component(self.max);
+                        let high = axis.ivec3_component(static_block.min) - shortfall(gap);
                         Some(Self {
-                            min: axis.set_ivec3_component(&self.min, axis.ivec3_component(static_block.min) - length),
-                            max: axis.set_ivec3_component(&self.max, axis.ivec3_component(static_block.min)),
+                            min: axis.set_ivec3_component(&self.min, high - length),
+                            max: axis.set_ivec3_component(&self.max, high),
                             ..*self
                         })
                     }
@@ -213,10 +456,12 @@ impl Block {
                         None
                     },
                 false =>
-                    if axis.ivec3_component(self.max) >= axis.ivec3_component(static_block.min) {
+                    if axis.ivec3_component(static_block.max) <= axis.ivec3_component(self.min) {
+                        let gap = axis.ivec3_component(self.min) - axis.ivec3_component(static_block.max);
+                        let low = axis.ivec3_component(static_block.max) + shortfall(gap);
                         Some(Self {
-                            min: axis.set_ivec3_component(&self.min, axis.ivec3_component(static_block.max)),
-                            max: axis.set_ivec3_component(&self.max, axis.ivec3_component(static_block.max) + length),
+                            min: axis.set_ivec3_component(&self.min, low),
+                            max: axis.set_ivec3_component(&self.max, low + length),
                             ..*self
                         })
                     }
@@ -230,3 +475,402 @@ impl Block {
         }
     }
 }
+
+// blocks grouped by the lines they occupy: for each axis, the cells of a block's cross-section
+// perpendicular to it. Anything that can be in front of a block shares one of those lines, so
+// front-block lookups only look at a handful of candidates instead of the whole board
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "engine", derive(Resource))]
+pub struct BlockIndex {
+    lines: HashMap<(Axis, IVec2), Vec<Block>>,
+}
+
+impl BlockIndex {
+    pub fn new<I>(blocks: I) -> Self
+    where
+        I: Iterator<Item=Block>
+    {
+        let mut index = Self::default();
+        blocks.for_each(|b| index.insert(b));
+        index
+    }
+
+    fn line_keys(block: &Block, axis: Axis) -> impl Iterator<Item=(Axis, IVec2)> {
+        let [a, b] = axis.remaining_two();
+        let (a_min, a_max) = (a.ivec3_component(block.min), a.ivec3_component(block.max));
+        let (b_min, b_max) = (b.ivec3_component(block.min), b.ivec3_component(block.max));
+        (a_min..a_max).flat_map(move |i| (b_min..b_max).map(move |j| (axis, IVec2::new(i, j))))
+    }
+
+    pub fn insert(self: &mut Self, block: Block) {
+        for axis in Axis::ALL {
+            for key in Self::line_keys(&block, axis) {
+                self.lines.entry(key).or_default().push(block);
+            }
+        }
+    }
+
+    pub fn remove(self: &mut Self, block: &Block) {
+        for axis in Axis::ALL {
+            for key in Self::line_keys(block, axis) {
+                if let Some(line) = self.lines.get_mut(&key) {
+                    line.retain(|b| b != block);
+                }
+            }
+        }
+    }
+
+    // most blocks sharing a single line of cells, along any axis
+    pub fn longest_line(self: &Self) -> usize {
+        self.lines.values().map(Vec::len).max().unwrap_or(0)
+    }
+
+    fn candidates(self: &Self, block: &Block) -> Vec<Block> {
+        let mut found: Vec<Block> = Vec::new();
+        for key in Self::line_keys(block, block.direction.axis) {
+            for b in self.lines.get(&key).into_iter().flatten() {
+                if !found.contains(b) {
+                    found.push(*b);
+                }
+            }
+        }
+        found
+    }
+
+    pub fn blocks_in_front(self: &Self, block: &Block) -> Vec<Block> {
+        block.get_blocks_in_front(self.candidates(block).into_iter())
+    }
+
+    pub fn nearest_block_in_front(self: &Self, block: &Block) -> Option<Block> {
+        block.get_nearest_block_in_front(self.candidates(block).into_iter())
+    }
+}
+
+// the same for any order of the same blocks, so equal positions hash equally
+pub fn board_hash(blocks: &[Block]) -> u64 {
+    let mut keys: Vec<_> = blocks.iter().map(Block::canonical_key).collect();
+    keys.sort_unstable();
+    let mut hasher = DefaultHasher::new();
+    keys.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(direction: Direction, min: IVec3) -> Block {
+        Block::new(direction, min, min + IVec3::ONE)
+    }
+
+    #[test]
+    fn nearest_block_in_front_ignores_slice_order() {
+        let moving = cube(Direction::XP, IVec3::ZERO);
+        let near = cube(Direction::YP, IVec3::new(2, 0, 0));
+        // centers at 3.5 and 6, closer together than the cells suggest
+        let long = Block::new(Direction::YP, IVec3::new(3, 0, 0), IVec3::new(5, 0, 0) + IVec3::Y + IVec3::Z);
+        let far = cube(Direction::YP, IVec3::new(6, 0, 0));
+        let orders = [
+            [near, long, far], [near, far, long], [long, near, far],
+            [long, far, near], [far, near, long], [far, long, near],
+        ];
+        for order in orders {
+            assert_eq!(moving.get_nearest_block_in_front(order.into_iter()), Some(near));
+        }
+    }
+
+    fn apply(board: &[Block], moves: &[(Block, Block)]) -> Vec<Block> {
+        board.iter()
+            .map(|b| moves.iter().find(|(old, _)| old == b).map_or(*b, |(_, new)| *new))
+            .collect()
+    }
+
+    fn assert_no_overlap(board: &[Block]) {
+        for (i, a) in board.iter().enumerate() {
+            for b in board.iter().skip(i + 1) {
+                assert!(!a.overlaps(b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn jammed_line_is_not_pushed() {
+        let pusher = cube(Direction::XP, IVec3::ZERO);
+        let board = [pusher, cube(Direction::YP, IVec3::new(1, 0, 0)), cube(Direction::ZP, IVec3::new(2, 0, 0))];
+        assert_eq!(pusher.push_line(&board, 3), None);
+    }
+
+    #[test]
+    fn partial_line_is_compacted_against_the_bound() {
+        let pusher = cube(Direction::XP, IVec3::ZERO);
+        let first = cube(Direction::YP, IVec3::new(2, 0, 0));
+        let second = cube(Direction::YN, IVec3::new(4, 0, 0));
+        let board = [pusher, first, second];
+        let moves = pusher.push_line(&board, 6).unwrap();
+        assert_eq!(apply(&board, &moves), vec![
+            cube(Direction::XP, IVec3::new(3, 0, 0)),
+            cube(Direction::YP, IVec3::new(4, 0, 0)),
+            cube(Direction::YN, IVec3::new(5, 0, 0)),
+        ]);
+    }
+
+    #[test]
+    fn wide_block_stops_at_a_block_on_the_next_line() {
+        let pusher = cube(Direction::XP, IVec3::ZERO);
+        // spans the pusher's line and the one above it, where another block sits further on
+        let wide = Block::new(Direction::ZP, IVec3::new(1, 0, 0), IVec3::new(2, 2, 1));
+        let beside = cube(Direction::ZN, IVec3::new(3, 1, 0));
+        let board = [pusher, wide, beside];
+        let moves = pusher.push_line(&board, 6).unwrap();
+        let after = apply(&board, &moves);
+        assert_no_overlap(&after);
+        assert_eq!(after[1], Block::new(Direction::ZP, IVec3::new(2, 0, 0), IVec3::new(3, 2, 1)));
+        assert_eq!(after[0], cube(Direction::XP, IVec3::new(1, 0, 0)));
+        assert_eq!(after[2], beside);
+    }
+
+    #[test]
+    fn pushed_blocks_keep_to_their_step() {
+        let pusher = cube(Direction::XN, IVec3::new(3, 0, 0));
+        let leaper = Block { step: 2, ..cube(Direction::YP, IVec3::new(2, 0, 0)) };
+        let board = [pusher, leaper];
+        // one free cell is too little for a block that only leaps two at a time
+        assert_eq!(pusher.push_line(&board, 1), None);
+        let moves = pusher.push_line(&board, 0).unwrap();
+        assert_eq!(apply(&board, &moves), vec![
+            cube(Direction::XN, IVec3::new(1, 0, 0)),
+            Block { step: 2, ..cube(Direction::YP, IVec3::ZERO) },
+        ]);
+    }
+
+    #[test]
+    fn three_long_block_slides_up_to_a_static_block() {
+        let long = Block::new(Direction::ZP, IVec3::ZERO, IVec3::new(1, 1, 3));
+        assert_eq!(long.get_elongation(), Some((Axis::Z, 3)));
+        let ahead = cube(Direction::XP, IVec3::new(0, 0, 5));
+        assert_eq!(long.move_block(&ahead), Some(Block::new(Direction::ZP, IVec3::new(0, 0, 2), IVec3::new(1, 1, 5))));
+        // sideways it only takes up one cell of the way
+        let sideways = Block { direction: Direction::XP, ..long };
+        let beside = cube(Direction::YP, IVec3::new(4, 0, 1));
+        assert_eq!(sideways.move_block(&beside), Some(Block::new(Direction::XP, IVec3::new(3, 0, 0), IVec3::new(4, 1, 3))));
+    }
+
+    #[test]
+    fn contains_point_is_half_open() {
+        let b = Block::new(Direction::XP, IVec3::ZERO, IVec3::new(1, 1, 2));
+        assert!(b.contains_point(IVec3::ZERO));
+        assert!(b.contains_point(IVec3::new(0, 0, 1)));
+        assert!(!b.contains_point(IVec3::new(0, 0, 2)));
+        assert!(!b.contains_point(IVec3::new(-1, 0, 0)));
+    }
+
+    #[test]
+    fn touching_blocks_do_not_overlap() {
+        let a = cube(Direction::XP, IVec3::ZERO);
+        for neighbour in [IVec3::X, IVec3::NEG_Y, IVec3::Z, IVec3::ONE] {
+            let b = cube(Direction::XP, neighbour);
+            assert!(!a.overlaps(&b) && !b.overlaps(&a));
+        }
+    }
+
+    #[test]
+    fn contained_blocks_overlap() {
+        let outer = Block::new(Direction::XP, IVec3::ZERO, IVec3::new(1, 3, 1));
+        let inner = cube(Direction::YN, IVec3::new(0, 1, 0));
+        assert!(outer.overlaps(&inner) && inner.overlaps(&outer));
+        assert!(outer.overlaps(&outer));
+    }
+
+    #[test]
+    fn disjoint_blocks_do_not_overlap() {
+        let a = cube(Direction::XP, IVec3::ZERO);
+        let b = Block::new(Direction::XP, IVec3::new(3, 0, 0), IVec3::new(5, 1, 1));
+        assert!(!a.overlaps(&b) && !b.overlaps(&a));
+    }
+
+    #[test]
+    fn from_center_size_lands_on_cells_around_negative_centers() {
+        assert_eq!(
+            Block::from_center_size(Direction::XP, Vec3::splat(-0.5), Vec3::ONE),
+            Some(cube(Direction::XP, IVec3::NEG_ONE)),
+        );
+        assert_eq!(
+            Block::from_center_size(Direction::XN, Vec3::new(-1.0, 0.5, -2.5), Vec3::new(2.0, 1.0, 1.0)),
+            Some(Block::new(Direction::XN, IVec3::new(-2, 0, -3), IVec3::new(0, 1, -2))),
+        );
+    }
+
+    #[test]
+    fn from_center_size_round_trips_blocks_anywhere() {
+        for min in [IVec3::new(-3, -1, 0), IVec3::new(2, -4, -2), IVec3::ZERO] {
+            for size in [IVec3::ONE, IVec3::new(2, 1, 1), IVec3::new(1, 1, 3)] {
+                let b = Block::new(Direction::ZN, min, min + size);
+                assert_eq!(Block::from_center_size(b.direction, b.get_center(), b.get_size()), Some(b));
+            }
+        }
+    }
+
+    #[test]
+    fn from_center_size_rejects_blocks_without_extent() {
+        assert_eq!(Block::from_center_size(Direction::XP, Vec3::new(-0.5, 0.5, 0.5), Vec3::new(0.0, 1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn board_hash_ignores_block_order() {
+        let a = cube(Direction::XP, IVec3::ZERO);
+        let b = Block::new(Direction::YN, IVec3::new(1, 0, 0), IVec3::new(2, 2, 1));
+        let c = cube(Direction::ZP, IVec3::new(0, 0, 1));
+        assert_eq!(board_hash(&[a, b, c]), board_hash(&[c, a, b]));
+        assert_ne!(board_hash(&[a, b]), board_hash(&[a, c]));
+    }
+
+    #[test]
+    fn far_apart_blocks_have_different_keys() {
+        let near = cube(Direction::XP, IVec3::ZERO);
+        let far = cube(Direction::XP, IVec3::new(256, 0, 0));
+        assert_ne!(near.canonical_key(), far.canonical_key());
+        assert_ne!(board_hash(&[near]), board_hash(&[far]));
+    }
+
+    #[test]
+    fn distance_to_exit_in_every_direction() {
+        let (lower, upper) = (IVec3::ZERO, IVec3::new(5, 6, 8));
+        let expected = [
+            (Direction::XP, 3), (Direction::XN, 1),
+            (Direction::YP, 3), (Direction::YN, 2),
+            (Direction::ZP, 4), (Direction::ZN, 3),
+        ];
+        for (direction, distance) in expected {
+            let b = cube(direction, IVec3::new(1, 2, 3));
+            assert_eq!(b.distance_to_exit(lower, upper), distance, "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn blocks_saved_before_steps_load_with_the_default_step() {
+        let old = r#"{ "direction": { "axis": "X", "positive": true }, "min": [0, 1, 2], "max": [1, 2, 4] }"#;
+        let b: Block = serde_json::from_str(old).unwrap();
+        assert_eq!(b, Block::new(Direction::XP, IVec3::new(0, 1, 2), IVec3::new(1, 2, 4)));
+        assert_eq!(b.step, Block::DEFAULT_STEP);
+    }
+
+    #[test]
+    fn step_round_trips() {
+        let b = Block { step: 2, ..cube(Direction::ZN, IVec3::new(-1, 0, 3)) };
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(serde_json::from_str::<Block>(&json).unwrap(), b);
+    }
+
+    #[test]
+    fn opposite_directions() {
+        for d in Direction::ALL {
+            let o = d.opposite();
+            assert_eq!((o.axis, o.positive), (d.axis, !d.positive));
+            assert_eq!(o.opposite(), d);
+            assert!(d.is_opposite(&o) && o.is_opposite(&d));
+            assert!(!d.is_opposite(&d));
+            assert_eq!(o.unit_vector(), -d.unit_vector());
+            for other in Direction::ALL.into_iter().filter(|other| other.axis != d.axis) {
+                assert!(!d.is_opposite(&other));
+            }
+        }
+    }
+
+    #[test]
+    fn exit_edge_clears_blocks_still_on_the_way_out() {
+        let leaving = cube(Direction::XP, IVec3::ZERO);
+        // one already past the bound on the same line, one beside it and one behind
+        let ahead = Block::new(Direction::XP, IVec3::new(5, 0, 0), IVec3::new(7, 1, 1));
+        let beside = cube(Direction::XP, IVec3::new(9, 1, 0));
+        let behind = cube(Direction::XP, IVec3::new(-3, 0, 0));
+        let present = [leaving, ahead, beside, behind];
+        assert_eq!(leaving.exit_edge(present.into_iter(), 4), 7);
+        assert_eq!(leaving.exit_edge([leaving, beside, behind].into_iter(), 4), 4);
+        let back = cube(Direction::XN, IVec3::new(3, 0, 0));
+        assert_eq!(back.exit_edge([back, behind, ahead].into_iter(), 0), -3);
+    }
+
+    #[test]
+    fn rotate_axis_to_axis_maps_one_axis_onto_the_other() {
+        let pairs = [(Axis::X, Axis::Y), (Axis::Y, Axis::Z), (Axis::Z, Axis::X)];
+        for (a, b) in pairs.into_iter().flat_map(|(a, b)| [(a, b), (b, a)]) {
+            let rotated = rotate_axis_to_axis(&a, &b).mul_vec3(a.unit_vector());
+            assert!(rotated.abs_diff_eq(b.unit_vector(), 1e-6), "{:?} to {:?} gives {}", a, b, rotated);
+        }
+        for a in Axis::ALL {
+            assert_eq!(rotate_axis_to_axis(&a, &a), Quat::IDENTITY);
+        }
+    }
+
+    #[test]
+    fn flip_if_necessary_turns_negative_directions_around() {
+        for d in Direction::ALL {
+            for axis in Axis::ALL {
+                let flip = flip_if_necessary(&d, &axis);
+                if d.positive {
+                    assert_eq!(flip, Quat::IDENTITY);
+                } else {
+                    // half a turn around `axis`: the axis stays, the other two reverse
+                    assert!(flip.mul_vec3(axis.unit_vector()).abs_diff_eq(axis.unit_vector(), 1e-6));
+                    for other in axis.remaining_two() {
+                        assert!(flip.mul_vec3(other.unit_vector()).abs_diff_eq(-other.unit_vector(), 1e-6));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn touching_block_ahead_stops_the_block_in_place() {
+        for direction in [Direction::XP, Direction::XN] {
+            let b = cube(direction, IVec3::new(3, 0, 0));
+            let touching = cube(Direction::YP, IVec3::new(3 + direction.sign(), 0, 0));
+            assert_eq!(b.get_nearest_block_in_front([touching].into_iter()), Some(touching));
+            assert_eq!(b.move_block(&touching), Some(b));
+        }
+    }
+
+    #[test]
+    fn block_behind_is_never_the_blocker() {
+        for direction in [Direction::XP, Direction::XN] {
+            let b = cube(direction, IVec3::new(3, 0, 0));
+            let behind = cube(Direction::YP, IVec3::new(3 - direction.sign(), 0, 0));
+            assert_eq!(b.get_nearest_block_in_front([behind].into_iter()), None);
+            assert_eq!(b.move_block(&behind), None);
+        }
+    }
+
+    #[test]
+    fn wide_block_partly_in_the_way_is_ahead() {
+        // a wide block one line over still blocks a wide mover sharing one of its lines
+        let mover = Block::new(Direction::ZP, IVec3::ZERO, IVec3::new(2, 1, 1));
+        let partly = Block::new(Direction::XP, IVec3::new(1, 0, 2), IVec3::new(3, 1, 3));
+        let clear = cube(Direction::XP, IVec3::new(2, 0, 1));
+        assert_eq!(mover.get_nearest_block_in_front([clear, partly].into_iter()), Some(partly));
+        assert_eq!(mover.move_block(&partly), Some(Block::new(Direction::ZP, IVec3::new(0, 0, 1), IVec3::new(2, 1, 2))));
+    }
+
+    #[test]
+    fn directions_round_trip_in_the_compact_form() {
+        for d in Direction::ALL {
+            let json = serde_json::to_string(&d).unwrap();
+            assert_eq!(json, format!("\"{}\"", d.name()));
+            assert_eq!(serde_json::from_str::<Direction>(&json).unwrap(), d);
+        }
+    }
+
+    #[test]
+    fn directions_still_read_the_long_form() {
+        for d in Direction::ALL {
+            let json = format!(r#"{{ "axis": "{:?}", "positive": {} }}"#, d.axis, d.positive);
+            assert_eq!(serde_json::from_str::<Direction>(&json).unwrap(), d);
+        }
+    }
+
+    #[test]
+    fn unknown_direction_names_are_rejected() {
+        for json in [r#""XQ""#, r#""xp""#, r#""""#, r#""XPX""#, "3"] {
+            assert!(serde_json::from_str::<Direction>(json).is_err(), "{}", json);
+        }
+    }
+}
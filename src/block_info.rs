@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::block::Block;
+use crate::selection::Selected;
+use crate::{FreeBlocks, HoveredBlock, LevelBounds};
+use crate::keybindings::{Action, KeyBindings};
+
+#[derive(Resource, Default)]
+pub struct ShowBlockInfo(pub bool);
+
+pub fn toggle_block_info(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut show_info: ResMut<ShowBlockInfo>,
+) {
+    if bindings.just_pressed(&keys, Action::BlockInfo) {
+        show_info.0 = !show_info.0;
+    }
+}
+
+// describes the selected block, or the hovered one while nothing is selected, so both the
+// keyboard selection and the mouse drive it. Redrawn every frame, so it follows moves live
+pub fn draw_block_info(
+    mut contexts: EguiContexts,
+    show_info: Res<ShowBlockInfo>,
+    selected: Res<Selected>,
+    hovered: Res<HoveredBlock>,
+    free: Res<FreeBlocks>,
+    level_bounds: Res<LevelBounds>,
+    blocks: Query<&Block>,
+) -> Result {
+    if !show_info.0 {
+        return Ok(());
+    }
+    let LevelBounds(lower, upper) = *level_bounds;
+    let shown = selected.0.or(hovered.0).and_then(|e| blocks.get(e).ok().map(|b| (e, b)));
+    egui::Window::new("Block")
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            let Some((entity, block)) = shown else {
+                ui.label("Select or hover a block");
+                return;
+            };
+            egui::Grid::new("block_info").num_columns(2).show(ui, |ui| {
+                let direction = format!("{}{:?}", if block.direction.positive { "+" } else { "-" }, block.direction.axis);
+                let elongation = block.get_elongation()
+                    .map_or(String::from("none"), |(axis, length)| format!("{:?} × {}", axis, length));
+                let rows = [
+                    ("Direction", direction),
+                    ("Min", block.min.to_string()),
+                    ("Max", block.max.to_string()),
+                    ("Size", block.get_isize().to_string()),
+                    ("Elongation", elongation),
+                    ("Cells to exit", block.distance_to_exit(lower, upper).to_string()),
+                    ("Free", free.0.contains(&entity).to_string()),
+                ];
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::{BlockActivated, LevelBounds};
+use crate::block::Block;
+use crate::settings::Settings;
+use crate::keybindings::{Action, KeyBindings};
+
+// extra room around the level so the outer blocks don't touch the window edge
+const FRAME_MARGIN: f32 = 1.2;
+
+// matches the view from the camera's spawn position at (0, 10, 20), i.e. pitch = atan(10 / 20)
+const HOME_YAW: f32 = 0.0;
+const HOME_PITCH: f32 = 0.4636;
+
+// keys 1-6 look along +X, -X, +Y, -Y, +Z, -Z as (yaw, pitch). Looking along Y keeps the
+// current yaw so the picture doesn't spin around the view axis
+const AXIS_VIEWS: [(KeyCode, Option<f32>, f32); 6] = [
+    (KeyCode::Digit1, Some(-FRAC_PI_2), 0.0),
+    (KeyCode::Digit2, Some(FRAC_PI_2), 0.0),
+    (KeyCode::Digit3, None, -FRAC_PI_2),
+    (KeyCode::Digit4, None, FRAC_PI_2),
+    (KeyCode::Digit5, Some(PI), 0.0),
+    (KeyCode::Digit6, Some(0.0), 0.0),
+];
+
+// canonical view of the current level, computed when it is framed
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct CameraHome {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub focus: Vec3,
+    pub radius: f32,
+}
+
+// block translations are in grid units (only the models are scaled by BLOCK_SCALE),
+// so the world-space extent of the level is just the size of its bounds
+fn level_radius(bounds: &LevelBounds) -> f32 {
+    let LevelBounds(lower, upper) = *bounds;
+    (upper - lower).as_vec3().length() / 2.0
+}
+
+// PanOrbitCamera radius at which a sphere of the given radius fits in the viewport. For a
+// perspective camera that's a distance, for an orthographic one it's the visible height
+fn fitting_distance(radius: f32, projection: &Projection) -> f32 {
+    match projection {
+        Projection::Perspective(perspective) => {
+            let vertical = perspective.fov;
+            let horizontal = 2.0 * ((vertical / 2.0).tan() * perspective.aspect_ratio).atan();
+            radius / (vertical.min(horizontal) / 2.0).sin()
+        },
+        Projection::Orthographic(orthographic) => {
+            let area = orthographic.area;
+            let aspect = if area.height() > 0.0 { area.width() / area.height() } else { 1.0 };
+            2.0 * radius / aspect.min(1.0)
+        },
+        Projection::Custom(_) => radius * 2.0,
+    }
+}
+
+pub fn frame_camera_to_level(
+    mut commands: Commands,
+    level_bounds: Res<LevelBounds>,
+    mut camera_query: Query<(&mut PanOrbitCamera, &Projection)>,
+) {
+    let radius = level_radius(&level_bounds);
+    for (mut camera, projection) in camera_query.iter_mut() {
+        let home = CameraHome {
+            yaw: HOME_YAW,
+            pitch: HOME_PITCH,
+            // the level is drawn around the origin, which is where LevelCenter ends up
+            focus: Vec3::ZERO,
+            radius: fitting_distance(radius, projection) * FRAME_MARGIN,
+        };
+        camera.target_focus = home.focus;
+        camera.target_radius = home.radius;
+        commands.insert_resource(home);
+    }
+}
+
+pub fn reframe_on_key(
+    commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    level_bounds: Res<LevelBounds>,
+    camera_query: Query<(&mut PanOrbitCamera, &Projection)>,
+) {
+    if bindings.just_pressed(&keys, Action::Reframe) {
+        frame_camera_to_level(commands, level_bounds, camera_query);
+    }
+}
+
+// the equivalent of `target` closest to `current`, so the camera never spins through extra turns
+fn nearest_angle(current: f32, target: f32) -> f32 {
+    let delta = (target - current).rem_euclid(TAU);
+    if delta > PI { current + delta - TAU } else { current + delta }
+}
+
+// only the targets are set, PanOrbitCamera's smoothing then eases the camera over
+pub fn return_camera_home(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    home: Res<CameraHome>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if !bindings.just_pressed(&keys, Action::CameraHome) {
+        return;
+    }
+    for mut camera in camera_query.iter_mut() {
+        camera.target_yaw = nearest_angle(camera.target_yaw, home.yaw);
+        camera.target_pitch = home.pitch;
+        camera.target_focus = home.focus;
+        camera.target_radius = home.radius;
+    }
+}
+
+// purely a viewing aid: picking and block positions don't depend on the projection
+pub fn toggle_orthographic(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    level_bounds: Res<LevelBounds>,
+    mut home: ResMut<CameraHome>,
+    mut camera_query: Query<(&mut PanOrbitCamera, &mut Projection)>,
+) {
+    if !bindings.just_pressed(&keys, Action::Orthographic) {
+        return;
+    }
+    for (mut camera, mut projection) in camera_query.iter_mut() {
+        *projection = match *projection {
+            Projection::Perspective(_) => Projection::Orthographic(OrthographicProjection {
+                // with a fixed height the camera's radius is the visible height in world units
+                scaling_mode: ScalingMode::FixedVertical { viewport_height: 1.0 },
+                ..OrthographicProjection::default_3d()
+            }),
+            _ => Projection::Perspective(PerspectiveProjection::default()),
+        };
+        // the radius means something different in each projection, so refit rather than keep it
+        let radius = fitting_distance(level_radius(&level_bounds), &projection) * FRAME_MARGIN;
+        camera.radius = Some(radius);
+        camera.target_radius = radius;
+        camera.force_update = true;
+        home.radius = radius;
+    }
+}
+
+pub fn snap_camera_to_axis(
+    keys: Res<ButtonInput<KeyCode>>,
+    home: Res<CameraHome>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    let Some(&(_, yaw, pitch)) = AXIS_VIEWS.iter().find(|(key, _, _)| keys.just_pressed(*key)) else { return };
+    for mut camera in camera_query.iter_mut() {
+        if let Some(yaw) = yaw {
+            camera.target_yaw = nearest_angle(camera.target_yaw, yaw);
+        }
+        camera.target_pitch = pitch;
+        camera.target_focus = home.focus;
+    }
+}
+
+// covers cameras spawned with PanOrbitCamera::default() as well as changes in the settings screen
+pub fn apply_camera_settings(
+    settings: Res<Settings>,
+    added: Query<(), Added<PanOrbitCamera>>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    if !settings.is_changed() && added.is_empty() {
+        return;
+    }
+    for mut camera in camera_query.iter_mut() {
+        camera.orbit_sensitivity = settings.orbit_sensitivity;
+        camera.pan_sensitivity = settings.pan_sensitivity;
+        camera.zoom_sensitivity = settings.zoom_sensitivity;
+    }
+}
+
+// target pitch of every orbit camera before PanOrbitCamera handled this frame's input
+#[derive(Resource, Default)]
+pub struct PitchBeforeOrbit(HashMap<Entity, f32>);
+
+pub fn remember_pitch(
+    mut before: ResMut<PitchBeforeOrbit>,
+    camera_query: Query<(Entity, &PanOrbitCamera)>,
+) {
+    before.0 = camera_query.iter().map(|(e, camera)| (e, camera.target_pitch)).collect();
+}
+
+// PanOrbitCamera can't invert the vertical orbit by itself, so the pitch change it made from
+// the mouse or touch this frame is mirrored right after. Pitch set by the view keys happens in
+// Update, before it's remembered, and isn't affected
+pub fn invert_orbit_pitch(
+    settings: Res<Settings>,
+    before: Res<PitchBeforeOrbit>,
+    mut camera_query: Query<(Entity, &mut PanOrbitCamera)>,
+) {
+    if !settings.invert_y {
+        return;
+    }
+    for (entity, mut camera) in camera_query.iter_mut() {
+        let Some(&pitch) = before.0.get(&entity) else { continue };
+        if camera.target_pitch != pitch {
+            camera.target_pitch = pitch - (camera.target_pitch - pitch);
+        }
+    }
+}
+
+// share of the way from the level's middle to a clicked block that the focus moves, so the
+// block's line stays in view without the level leaving it
+const AUTO_FOCUS_PULL: f32 = 0.3;
+
+// only the target is set, once per click: PanOrbitCamera eases over to it, and panning or
+// orbiting meanwhile simply takes over from there
+pub fn focus_on_moved_block(
+    settings: Res<Settings>,
+    mut activated: EventReader<BlockActivated>,
+    home: Res<CameraHome>,
+    blocks: Query<&Transform, With<Block>>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    let Some(BlockActivated(entity_id)) = activated.read().last() else { return };
+    if !settings.auto_focus_on_move {
+        return;
+    }
+    let Ok(tr) = blocks.get(*entity_id) else { return };
+    for mut camera in camera_query.iter_mut() {
+        camera.target_focus = home.focus.lerp(tr.translation, AUTO_FOCUS_PULL);
+    }
+}
@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::block::{Axis, Block};
+use crate::{LevelBounds, LevelCenter};
+use crate::keybindings::{Action, KeyBindings};
+
+// a plane through the level, in grid units. Blocks whose center is on the camera's side of it
+// are hidden so the inside of a dense cube shows. Only present while the cutaway is on
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct CutawayPlane {
+    pub axis: Axis,
+    pub position: f32,
+}
+
+fn axis_range(axis: Axis, level_bounds: &LevelBounds) -> (f32, f32) {
+    let LevelBounds(lower, upper) = *level_bounds;
+    (axis.ivec3_component(lower) as f32, axis.ivec3_component(upper) as f32)
+}
+
+// C starts the cutaway through the middle of the level, horizontally, and turns it off again
+pub fn toggle_cutaway(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    plane: Option<Res<CutawayPlane>>,
+    level_bounds: Res<LevelBounds>,
+) {
+    if !bindings.just_pressed(&keys, Action::Cutaway) {
+        return;
+    }
+    if plane.is_some() {
+        commands.remove_resource::<CutawayPlane>();
+        return;
+    }
+    let axis = Axis::Y;
+    let (low, high) = axis_range(axis, &level_bounds);
+    commands.insert_resource(CutawayPlane { axis, position: (low + high) / 2.0 });
+}
+
+// PageUp and PageDown move the plane by a cell, V turns it to face the next axis
+pub fn adjust_cutaway(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut plane: ResMut<CutawayPlane>,
+    level_bounds: Res<LevelBounds>,
+) {
+    if bindings.just_pressed(&keys, Action::CutawayAxis) {
+        plane.axis = plane.axis.next_rh();
+        let (low, high) = axis_range(plane.axis, &level_bounds);
+        plane.position = (low + high) / 2.0;
+    }
+    let step = if keys.just_pressed(KeyCode::PageUp) {
+        1.0
+    } else if keys.just_pressed(KeyCode::PageDown) {
+        -1.0
+    } else {
+        return;
+    };
+    let (low, high) = axis_range(plane.axis, &level_bounds);
+    plane.position = (plane.position + step).clamp(low, high);
+}
+
+// only visibility changes, the blocks stay on the board. Hidden blocks aren't picked either,
+// so the ones inside can be clicked through the gap
+pub fn apply_cutaway(
+    plane: Option<Res<CutawayPlane>>,
+    level_center: Res<LevelCenter>,
+    camera_query: Query<&GlobalTransform, With<PanOrbitCamera>>,
+    mut blocks: Query<(&Transform, &mut Visibility), With<Block>>,
+) {
+    let cut = plane.zip(camera_query.single().ok()).map(|(plane, camera)| {
+        let camera_side = plane.axis.vec3_component(camera.translation() + level_center.0) - plane.position;
+        (*plane, camera_side)
+    });
+    for (tr, mut visibility) in blocks.iter_mut() {
+        let hidden = cut.is_some_and(|(plane, camera_side)| {
+            let side = plane.axis.vec3_component(tr.translation + level_center.0) - plane.position;
+            side * camera_side > 0.0
+        });
+        visibility.set_if_neq(if hidden { Visibility::Hidden } else { Visibility::Inherited });
+    }
+}
+
+// the outline of the plane across the level, so it's clear where the cut is
+pub fn draw_cutaway_plane(
+    mut gizmos: Gizmos,
+    plane: Res<CutawayPlane>,
+    level_bounds: Res<LevelBounds>,
+    level_center: Res<LevelCenter>,
+) {
+    let LevelBounds(lower, upper) = *level_bounds;
+    let (lower, upper) = (lower.as_vec3(), upper.as_vec3());
+    let axis = plane.axis.unit_vector();
+    let center = lower.midpoint(upper) * (Vec3::ONE - axis) + axis * plane.position;
+    let size = (upper - lower) * (Vec3::ONE - axis);
+    gizmos.cuboid(
+        Transform::from_translation(center - level_center.0).with_scale(size),
+        Color::srgba(1.0, 0.85, 0.1, 0.6),
+    );
+}
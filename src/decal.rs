@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+use crate::block::Block;
+use crate::BLOCK_SCALE;
+
+// a small square on the face a block leaves through, so its direction shows without relying on
+// color alone
+#[derive(Component)]
+pub struct ExitDecal;
+
+// side of the square in grid units, and how far it floats off the face to avoid z-fighting
+const DECAL_SIZE: f32 = 0.4;
+const DECAL_LIFT: f32 = 0.01;
+
+// one mesh for every decal. Materials are made per block instead, since fading and highlighting
+// a block changes the materials below it
+#[derive(Resource)]
+pub struct DecalMesh(Handle<Mesh>);
+
+pub fn create_decal_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let side = DECAL_SIZE / BLOCK_SCALE;
+    commands.insert_resource(DecalMesh(meshes.add(Rectangle::new(side, side))));
+}
+
+// block_model_rotation turns the model's +Y onto the block's direction, so in the block's own
+// space the leading face is always on +Y. That keeps the decal right when the board is rotated
+// or mirrored and the block's rotation is worked out again
+pub fn add_exit_decal(
+    trigger: Trigger<SceneInstanceReady>,
+    mut commands: Commands,
+    blocks: Query<&Block>,
+    mesh: Res<DecalMesh>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let root = trigger.target();
+    let Ok(block) = blocks.get(root) else { return };
+    let half_length = block.direction.axis.vec3_component(block.get_size()) / 2.0;
+    commands.entity(root).with_child((
+        Mesh3d(mesh.0.clone()),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(0.95, 0.95, 0.95),
+            unlit: true,
+            ..default()
+        })),
+        Transform::from_translation(Vec3::Y * (half_length + DECAL_LIFT) / BLOCK_SCALE)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, Vec3::Y)),
+        ExitDecal,
+    ));
+}
@@ -0,0 +1,26 @@
+use bevy::prelude::*;
+use crate::{Interface, LevelLoadingState, PlayingSet, fps_overlay, overlay, stats_panel};
+
+// only built with `--features dev`: the world inspector and the debug panels (F3 frame rate,
+// F9 locked blocks, F10 level stats). Release builds leave all of them out, while the egui
+// panels that are part of the game itself stay in either way
+pub struct DevPlugin;
+
+impl Plugin for DevPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins((
+                bevy_inspector_egui::quick::WorldInspectorPlugin::new(),
+                bevy::diagnostic::FrameTimeDiagnosticsPlugin::default(),
+            ))
+            .init_resource::<stats_panel::ShowLevelStats>()
+            .init_resource::<stats_panel::CurrentLevelStats>()
+            .add_systems(Startup, fps_overlay::spawn_fps_overlay)
+            .add_systems(Update, (fps_overlay::toggle_fps_overlay, fps_overlay::update_fps_overlay).chain())
+            .add_systems(Update, overlay::toggle_locked_blocks.in_set(PlayingSet))
+            .add_systems(Update, (stats_panel::toggle_level_stats, stats_panel::update_level_stats).chain().in_set(PlayingSet))
+            .add_systems(bevy_egui::EguiPrimaryContextPass, stats_panel::draw_level_stats
+                .run_if(in_state(Interface::Gameplay))
+                .run_if(in_state(LevelLoadingState::Level)));
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::block::{Block, BlockIndex};
+use crate::{BlockActivated, ClickableBlock, Interface, LevelBounds, TAP_SLOP};
+
+// block held by the pointer, previewed along its direction until it's released
+#[derive(Resource, Default)]
+pub struct BlockDrag(Option<HeldBlock>);
+
+pub struct HeldBlock {
+    entity: Entity,
+    start: Vec3,
+    // cells to the first stop: against the next block, or past the edge of the level
+    stop: f32,
+    // a press only takes the drag from the camera once it has moved along the block's axis
+    engaged: bool,
+}
+
+// the preview can nudge a block that has another one right in front of it, which pushes the line
+const MIN_PREVIEW: f32 = 0.5;
+
+fn distance_to_stop(block: &Block, index: &BlockIndex, level_bounds: &LevelBounds) -> f32 {
+    let LevelBounds(lower, upper) = *level_bounds;
+    match index.nearest_block_in_front(block).and_then(|b| block.move_block(&b)) {
+        Some(moved) => (moved.get_center() - block.get_center()).length(),
+        None => block.distance_to_exit(lower, upper) as f32 + 1.0,
+    }
+}
+
+// one cell along the block's direction, as it appears on screen
+fn cell_on_screen(
+    held: &HeldBlock,
+    block: &Block,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let dir = block.direction.unit_vector();
+    let (Ok(from), Ok(to)) = (
+        camera.world_to_viewport(camera_transform, held.start),
+        camera.world_to_viewport(camera_transform, held.start + dir),
+    ) else { return None };
+    // looking straight down the block's axis, there's nothing to drag along
+    Some(to - from).filter(|cell| cell.length_squared() >= 1.0)
+}
+
+// the pointer's travel in cells along the block's direction
+fn dragged_cells(distance: Vec2, cell_on_screen: Option<Vec2>) -> f32 {
+    cell_on_screen.map_or(0.0, |cell| distance.dot(cell) / cell.length_squared())
+}
+
+// past the tap threshold and mostly along the block's axis, either way. Anything else is the
+// camera orbiting, even when it started on a block
+fn is_block_drag(distance: Vec2, cell_on_screen: Option<Vec2>) -> bool {
+    let Some(axis) = cell_on_screen.map(Vec2::normalize) else { return false };
+    distance.length() > TAP_SLOP && distance.dot(axis).abs() > distance.perp_dot(axis).abs()
+}
+
+pub fn grab_block(
+    press: Trigger<Pointer<Pressed>>,
+    blocks: Query<(&Block, &Transform), ClickableBlock>,
+    index: Res<BlockIndex>,
+    level_bounds: Option<Res<LevelBounds>>,
+    interface: Res<State<Interface>>,
+    mut drag: ResMut<BlockDrag>,
+) {
+    if *interface.get() != Interface::Gameplay || press.button != PointerButton::Primary {
+        return;
+    }
+    let (Ok((block, tr)), Some(level_bounds)) = (blocks.get(press.target()), level_bounds) else { return };
+    drag.0 = Some(HeldBlock {
+        entity: press.target(),
+        start: tr.translation,
+        stop: distance_to_stop(block, &index, &level_bounds),
+        engaged: false,
+    });
+}
+
+pub fn preview_block_drag(
+    event: Trigger<Pointer<Drag>>,
+    mut drag: ResMut<BlockDrag>,
+    mut blocks: Query<(&Block, &mut Transform)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+) {
+    let Some(held) = drag.0.as_mut().filter(|held| held.entity == event.target()) else { return };
+    let (Ok((block, mut tr)), Ok((camera, camera_transform))) = (blocks.get_mut(held.entity), camera_query.single()) else { return };
+    let cell = cell_on_screen(held, block, camera, camera_transform);
+    if !held.engaged {
+        if is_block_drag(event.distance, cell) {
+            held.engaged = true;
+        } else {
+            if event.distance.length() > TAP_SLOP {
+                // the camera has it
+                drag.0 = None;
+            }
+            return;
+        }
+    }
+    let cells = dragged_cells(event.distance, cell);
+    tr.translation = held.start + block.direction.unit_vector() * cells.clamp(0.0, held.stop.max(MIN_PREVIEW));
+}
+
+// the block goes back to where it was picked up, and is only sent on if it was dragged
+// closer to its stop than to its start. Short drags are left to send_block_on_click
+pub fn release_block_drag(
+    event: Trigger<Pointer<DragEnd>>,
+    mut drag: ResMut<BlockDrag>,
+    mut blocks: Query<(&Block, &mut Transform)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    mut activated: EventWriter<BlockActivated>,
+) {
+    let Some(held) = drag.0.take_if(|held| held.entity == event.target()) else { return };
+    let Ok((block, mut tr)) = blocks.get_mut(held.entity) else { return };
+    tr.translation = held.start;
+    if !held.engaged {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_query.single() else { return };
+    let cells = dragged_cells(event.distance, cell_on_screen(&held, block, camera, camera_transform));
+    if cells >= held.stop.max(MIN_PREVIEW * 2.0) / 2.0 {
+        activated.write(BlockActivated(held.entity));
+    }
+}
+
+// a press that never turned into a drag ends up here once the pointer is let go
+pub fn drop_released_block(
+    mouse: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    mut drag: ResMut<BlockDrag>,
+) {
+    if drag.0.is_some() && !mouse.pressed(MouseButton::Left) && touches.iter().next().is_none() {
+        drag.0 = None;
+    }
+}
+
+// the camera would otherwise orbit along with the drag. A block that's only been pressed
+// leaves it alone, so a drag starting on a block can still orbit
+pub fn lock_camera_while_dragging(
+    drag: Res<BlockDrag>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    for mut camera in camera_query.iter_mut() {
+        camera.enabled = !drag.0.as_ref().is_some_and(|held| held.engaged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    // a block whose axis points to the right on screen, 40 pixels per cell
+    const CELL: Option<Vec2> = Some(Vec2::new(40.0, 0.0));
+
+    fn held(engaged: bool) -> BlockDrag {
+        BlockDrag(Some(HeldBlock { entity: Entity::PLACEHOLDER, start: Vec3::ZERO, stop: 3.0, engaged }))
+    }
+
+    fn camera_enabled(drag: BlockDrag) -> bool {
+        let mut world = World::new();
+        world.insert_resource(drag);
+        let camera = world.spawn(PanOrbitCamera::default()).id();
+        world.run_system_once(lock_camera_while_dragging).unwrap();
+        world.get::<PanOrbitCamera>(camera).unwrap().enabled
+    }
+
+    #[test]
+    fn drag_along_the_axis_takes_the_block() {
+        assert!(is_block_drag(Vec2::new(30.0, 10.0), CELL));
+        assert!(is_block_drag(Vec2::new(-30.0, 10.0), CELL));
+        assert_eq!(dragged_cells(Vec2::new(60.0, 10.0), CELL), 1.5);
+        assert!(!camera_enabled(held(true)));
+    }
+
+    #[test]
+    fn drag_across_the_axis_orbits() {
+        assert!(!is_block_drag(Vec2::new(10.0, 30.0), CELL));
+        // nothing to drag along when the axis points at the camera
+        assert!(!is_block_drag(Vec2::new(30.0, 0.0), None));
+        // a pressed block doesn't hold the camera until the drag is its own
+        assert!(camera_enabled(held(false)));
+        assert!(camera_enabled(BlockDrag::default()));
+    }
+
+    #[test]
+    fn small_moves_are_taps() {
+        assert!(!is_block_drag(Vec2::new(TAP_SLOP, 0.0), CELL));
+    }
+}
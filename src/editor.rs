@@ -0,0 +1,178 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::block::{Block, Direction};
+use crate::{
+    BlockModels, ButtonAction, HoveredBlock, Interface, Level,
+    button, spawn_block, write_level_file,
+};
+
+// highest layer a block can be stacked to in the editor
+const MAX_HEIGHT: i32 = 16;
+
+// the blocks being edited, kept while the level is tested in gameplay
+#[derive(Resource, Default)]
+pub struct EditedLevel(pub Vec<Block>);
+
+#[derive(Component)]
+pub struct EditorMarker;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(Interface::Editor), setup_editor)
+            .add_systems(OnExit(Interface::Editor), despawn_editor)
+            .add_systems(Update, (editor_button_system, rotate_hovered_block)
+                .run_if(in_state(Interface::Editor)));
+    }
+}
+
+fn spawn_editor_block(commands: &mut Commands, block: &Block, models: &BlockModels) {
+    let entity = spawn_block(commands, block, models, Vec3::ZERO);
+    commands.entity(entity)
+        .insert(EditorMarker)
+        .observe(delete_block_on_right_click);
+}
+
+fn setup_editor(
+    mut commands: Commands,
+    models: Res<BlockModels>,
+    edited_level: Option<Res<EditedLevel>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        Camera3d::default(),
+        PanOrbitCamera::default(),
+        Transform::from_xyz(0.0, 10.0, 20.0).looking_at(Vec3::ZERO, Vec3::Y),
+        EditorMarker,
+    ));
+    commands.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(3.0, 3.0, 3.0).looking_at(Vec3::ZERO, Vec3::Y),
+        EditorMarker,
+    ));
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(32.0, 32.0))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.3, 0.35))),
+        Transform::default(),
+        EditorMarker,
+    ))
+    .observe(place_block_on_click);
+    if let Some(edited_level) = edited_level {
+        for b in edited_level.0.iter() {
+            spawn_editor_block(&mut commands, b, &models);
+        }
+    }
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        children![
+            button("Test", ButtonAction::TestLevel),
+            button("Save", ButtonAction::SaveLevel),
+            button("Back to menu", ButtonAction::BackToMenu),
+        ],
+        EditorMarker,
+    ));
+}
+
+fn despawn_editor(
+    mut commands: Commands,
+    editor_query: Query<Entity, With<EditorMarker>>,
+) {
+    editor_query.iter().for_each(|e| commands.entity(e).despawn());
+}
+
+// new blocks are stacked on top of whatever already occupies the clicked column
+fn place_block_on_click(
+    click: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+    blocks: Query<&Block, With<EditorMarker>>,
+    models: Res<BlockModels>,
+) {
+    if click.event.button != PointerButton::Primary {
+        return;
+    }
+    let Some(position) = click.event.hit.position else { return };
+    let column = position.floor().as_ivec3();
+    let free_y = (0..MAX_HEIGHT)
+        .find(|y| blocks.iter().all(|b| !b.contains_point(column.with_y(*y))));
+    let Some(y) = free_y else { return };
+    let min = column.with_y(y);
+    let block = Block::new(Direction::XP, min, min + IVec3::ONE);
+    spawn_editor_block(&mut commands, &block, &models);
+}
+
+fn delete_block_on_right_click(
+    click: Trigger<Pointer<Click>>,
+    mut commands: Commands,
+) {
+    if click.event.button == PointerButton::Secondary {
+        commands.entity(click.target()).despawn();
+    }
+}
+
+fn rotate_hovered_block(
+    mut commands: Commands,
+    mut wheel: EventReader<MouseWheel>,
+    mut hovered: ResMut<HoveredBlock>,
+    blocks: Query<&Block, With<EditorMarker>>,
+    models: Res<BlockModels>,
+) {
+    let scroll: f32 = wheel.read().map(|w| w.y).sum();
+    if scroll == 0.0 {
+        return;
+    }
+    let Some(entity) = hovered.0 else { return };
+    let Ok(block) = blocks.get(entity) else { return };
+    let current = Direction::ALL.iter().position(|d| *d == block.direction).unwrap_or(0);
+    let step = if scroll > 0.0 { 1 } else { Direction::ALL.len() - 1 };
+    let rotated = Block { direction: Direction::ALL[(current + step) % Direction::ALL.len()], ..*block };
+    // respawning picks up the new model rotation and tint
+    commands.entity(entity).despawn();
+    spawn_editor_block(&mut commands, &rotated, &models);
+    hovered.0 = None;
+}
+
+fn editor_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    blocks: Query<&Block, With<EditorMarker>>,
+    mut istate: ResMut<NextState<Interface>>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let current: Vec<Block> = blocks.iter().copied().collect();
+        match *action {
+            ButtonAction::TestLevel => {
+                if current.is_empty() {
+                    continue;
+                }
+                commands.insert_resource(EditedLevel(current));
+                istate.set(Interface::Gameplay);
+            },
+            ButtonAction::SaveLevel => {
+                match write_level_file(&Level(current)) {
+                    Ok(path) => info!("saved level to {}", path.display()),
+                    Err(err) => error!("failed to save level: {}", err),
+                }
+            },
+            ButtonAction::BackToMenu => {
+                commands.remove_resource::<EditedLevel>();
+                istate.set(Interface::Menu);
+            },
+            _ => (),
+        }
+    }
+}
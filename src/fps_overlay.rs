@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use crate::block::Block;
+use crate::Animating;
+
+// a small corner readout for performance work, much lighter than the inspector
+#[derive(Component)]
+pub struct FpsOverlay;
+
+// not tied to a screen like the toasts, so it stays up across menus and levels
+pub fn spawn_fps_overlay(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.6, 1.0, 0.6)),
+        TextShadow::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+        Visibility::Hidden,
+        GlobalZIndex(2),
+        FpsOverlay,
+    ));
+}
+
+pub fn toggle_fps_overlay(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut overlays: Query<&mut Visibility, With<FpsOverlay>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visibility in overlays.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+pub fn update_fps_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    blocks: Query<(), With<Block>>,
+    animating: Query<(), Animating>,
+    mut overlays: Query<(&mut Text, &Visibility), With<FpsOverlay>>,
+) {
+    let smoothed = |path: &DiagnosticPath| diagnostics.get(path).and_then(|d| d.smoothed()).unwrap_or(0.0);
+    for (mut text, visibility) in overlays.iter_mut() {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        text.0 = format!(
+            "{:.0} fps  {:.1} ms\n{} blocks  {} animating",
+            smoothed(&FrameTimeDiagnosticsPlugin::FPS),
+            smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+            blocks.iter().count(),
+            animating.iter().count(),
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::block::Block;
+use crate::selection::{Selected, activate_selection, step_selection};
+use crate::settings::Settings;
+use crate::{BlockActivated, ClickableBlock};
+
+// how far a stick has to be pushed to count, and how far back to re-arm the cursor
+const STICK_PRESS: f32 = 0.6;
+const STICK_RELEASE: f32 = 0.3;
+// radians per second at full right-stick deflection
+const ORBIT_SPEED: f32 = 2.5;
+
+// the left stick moves the cursor one block per push, like a key press
+pub fn gamepad_move_selection(
+    gamepads: Query<&Gamepad>,
+    mut selected: ResMut<Selected>,
+    camera_query: Query<&GlobalTransform, With<PanOrbitCamera>>,
+    blocks: Query<(Entity, &Transform), With<Block>>,
+    mut armed: Local<bool>,
+) {
+    let Some(stick) = gamepads.iter()
+        .map(|gamepad| gamepad.left_stick())
+        .max_by(|s1, s2| s1.length_squared().total_cmp(&s2.length_squared()))
+    else { return };
+    if stick.length() < STICK_RELEASE {
+        *armed = true;
+        return;
+    }
+    if !*armed || stick.length() < STICK_PRESS {
+        return;
+    }
+    *armed = false;
+    let Ok(camera) = camera_query.single() else { return };
+    // snap to the dominant stick axis so diagonals don't pick surprising blocks
+    let screen_dir = if stick.x.abs() > stick.y.abs() { Vec2::X * stick.x.signum() } else { Vec2::Y * stick.y.signum() };
+    step_selection(&mut selected, screen_dir, camera, &blocks);
+}
+
+pub fn gamepad_activate_selected(
+    gamepads: Query<&Gamepad>,
+    selected: Res<Selected>,
+    clickable: Query<(), ClickableBlock>,
+    mut activated: EventWriter<BlockActivated>,
+) {
+    if gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::South)) {
+        activate_selection(&selected, &clickable, &mut activated);
+    }
+}
+
+pub fn gamepad_orbit_camera(
+    gamepads: Query<&Gamepad>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut camera_query: Query<&mut PanOrbitCamera>,
+) {
+    let stick: Vec2 = gamepads.iter().map(|gamepad| gamepad.right_stick()).sum();
+    if stick.length() < STICK_RELEASE {
+        return;
+    }
+    let mut step = stick * ORBIT_SPEED * time.delta_secs();
+    if settings.invert_y {
+        step.y = -step.y;
+    }
+    for mut camera in camera_query.iter_mut() {
+        camera.target_yaw -= step.x;
+        camera.target_pitch = (camera.target_pitch - step.y).clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+    }
+}
+
+pub fn gamepad_pause_pressed(gamepads: &Query<&Gamepad>) -> bool {
+    gamepads.iter().any(|gamepad| gamepad.any_just_pressed([GamepadButton::East, GamepadButton::Start]))
+}
@@ -1,7 +1,7 @@
 use crate::block::*;
-use bevy::math::{IVec2, IVec3, Vec2, Vec3};
-use rand::{Rng, rngs::ThreadRng};
-use rand::prelude::*;
+use glam::{IVec2, IVec3};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::collections::{HashMap, VecDeque};
 
 pub enum Tree {
     Leaf(GBlock),
@@ -62,24 +62,151 @@ pub enum Width {
     More,
 }
 
-pub fn width(x: i32) -> Width {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GenError {
+    // a seed with an empty or inverted range along some axis
+    BadWidth(i32),
+    // every attempt came out empty, overlapping or unsolvable
+    NoPlayableLevel { attempts: u32 },
+}
+
+impl std::fmt::Display for GenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadWidth(w) => write!(f, "cannot generate a level {} cells wide, every axis needs at least 1", w),
+            Self::NoPlayableLevel { attempts } => write!(f, "no playable level after {} attempts", attempts),
+        }
+    }
+}
+
+impl std::error::Error for GenError {}
+
+pub fn width(x: i32) -> Result<Width, GenError> {
     match x {
-        1 => Width::One,
-        2 => Width::Two,
-        n if n >= 2 => Width::More,
-        _ => panic!("wrong width"),
+        1 => Ok(Width::One),
+        2 => Ok(Width::Two),
+        n if n > 2 => Ok(Width::More),
+        n => Err(GenError::BadWidth(n)),
     }
 }
 
-pub fn random_direction(rng: &mut ThreadRng) -> Direction {
-    let axis = match rng.random_range(0..3) {
-        0 => Axis::X,
-        1 => Axis::Y,
-        2 => Axis::Z,
-        _ => panic!("random_direction: wrong axis index"),
-    };
-    let positive = rng.random_bool(0.5);
-    Direction { axis, positive }
+pub fn random_direction<R: Rng>(rng: &mut R) -> Direction {
+    Direction::ALL[rng.random_range(0..Direction::ALL.len())]
+}
+
+// tunes how densely and how finely the generator fills the volume. `align_prob` is the
+// chance a block is forced to point the positive way along its axis; blocks that agree
+// can't face each other, so higher values make for fewer deadlocks
+#[derive(Debug, Clone, Copy)]
+pub struct GenParams {
+    pub branch_prob: f32,
+    pub fill_prob: f32,
+    pub align_prob: f32,
+}
+
+impl Default for GenParams {
+    fn default() -> Self {
+        Self { branch_prob: 0.5, fill_prob: 0.5, align_prob: 0.0 }
+    }
+}
+
+const FILL_PROB_PER_LEVEL: f32 = 0.03;
+const MAX_FILL_PROB: f32 = 0.85;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 3] = [Self::Easy, Self::Medium, Self::Hard];
+
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Medium => "Medium",
+            Self::Hard => "Hard",
+        }
+    }
+
+    pub fn params(self: &Self) -> GenParams {
+        match self {
+            Self::Easy => GenParams { branch_prob: 0.3, fill_prob: 0.35, align_prob: 0.7 },
+            Self::Medium => GenParams::default(),
+            Self::Hard => GenParams { branch_prob: 0.7, fill_prob: 0.75, align_prob: 0.0 },
+        }
+    }
+
+    // the fill ramps up with the level on its own, so the bigger cubes of later levels don't
+    // come out mostly empty. Level 1 gets the plain params, and the fill never goes past
+    // MAX_FILL_PROB unless the difficulty starts out above it
+    pub fn level_params(self: &Self, level: u8) -> GenParams {
+        let base = self.params();
+        let ramped = base.fill_prob + FILL_PROB_PER_LEVEL * level.saturating_sub(1) as f32;
+        GenParams { fill_prob: ramped.min(MAX_FILL_PROB.max(base.fill_prob)), ..base }
+    }
+
+    // Medium keeps the original ramp of a 3-wide cube on level 1
+    pub fn side_len(self: &Self, level: u8) -> u8 {
+        match self {
+            Self::Easy => level.saturating_add(1).max(2),
+            Self::Medium => level.saturating_add(2),
+            Self::Hard => level.saturating_add(3),
+        }
+    }
+}
+
+// how the generator is tuned for a level of the campaign at the default difficulty
+pub fn difficulty_params(level: u8) -> GenParams {
+    Difficulty::default().level_params(level)
+}
+
+// endless mode grows the cube by one every `clears_per_step` cleared levels, up to a size
+// that is still comfortable to play
+#[derive(Debug, Clone, Copy)]
+pub struct EndlessRamp {
+    pub start_side_len: u8,
+    pub clears_per_step: u32,
+    pub max_side_len: u8,
+}
+
+impl Default for EndlessRamp {
+    fn default() -> Self {
+        Self { start_side_len: 2, clears_per_step: 3, max_side_len: 6 }
+    }
+}
+
+impl EndlessRamp {
+    pub fn side_len(self: &Self, cleared: u32) -> u8 {
+        let grown = self.start_side_len as u32 + cleared / self.clears_per_step.max(1);
+        grown.min(self.max_side_len as u32) as u8
+    }
+}
+
+// elongated blocks point along their long axis half of the time
+pub fn biased_direction<R: Rng>(rng: &mut R, long_axis: Option<&Axis>) -> Direction {
+    match long_axis {
+        Some(axis) if rng.random_bool(0.5) => Direction::new(*axis, rng.random_bool(0.5)),
+        _ => random_direction(rng),
+    }
+}
+
+fn gen_leaf<R: Rng>(rng: &mut R, params: &GenParams, seed: &Seed, long_axis: Option<&Axis>) -> Tree {
+    let (min, max) = seed.to_min_max();
+    let filled: bool = rng.random_bool(params.fill_prob as f64);
+    if filled {
+        let mut dir = biased_direction(rng, long_axis);
+        if rng.random_bool(params.align_prob as f64) {
+            dir.positive = true;
+        }
+        Tree::Leaf(GBlock::new(Some(dir), min, max))
+    }
+    else {
+        Tree::Leaf(GBlock::new(None, min, max))
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -97,165 +224,734 @@ impl GBlock {
 
 pub fn gblock_to_block(gb: &GBlock) -> Option<Block> {
     let &GBlock { direction: ref odir, min, max } = gb;
-    odir.clone().map(|direction| Block { direction, min, max })
+    odir.clone().map(|direction| Block::new(direction, min, max))
 }
 
 pub fn gblocks_to_blocks(gb: &[GBlock]) -> Vec<Block> {
     gb.iter().filter_map(gblock_to_block).collect()
 }
 
-// TODO: branches
-pub fn gen_tree(rng: &mut ThreadRng, seed: Seed) -> Tree {
+pub fn gen_tree<R: Rng>(rng: &mut R, params: &GenParams, seed: Seed) -> Result<Tree, GenError> {
     let Seed { x: (xmin, xmax), y: (ymin, ymax), z: (zmin, zmax) } = seed;
-    let xwidth = xmax - xmin;
-    let ywidth = ymax - ymin;
-    let zwidth = zmax - zmin;
-    let widths: Vec<Width> = [xwidth, ywidth, zwidth]
-        .iter().map(|x| width(*x)).collect();
-    let ones: usize = widths.iter().filter(|w: &&Width| **w == Width::One).count();
-    let twos: usize = widths.iter().filter(|w: &&Width| **w == Width::Two).count();
-    let (min, max) = seed.to_min_max();
-    match (ones, twos) {
-        (3, 0) => {
-            let filled: bool = rng.random_bool(0.5);
-            if filled {
-                let dir = random_direction(rng);
-                Tree::Leaf(GBlock::new(Some(dir), min, max))
-            }
-            else {
-                Tree::Leaf(GBlock::new(None, min, max))
-            }
-        },
-        (2, 1) => {
-            let axis = widths.iter()
-                .zip(Axis::ALL.iter()).filter(|(w, _)| **w == Width::Two)
-                .next().unwrap().1;
-            let split = rng.random_bool(0.5);
-            if split {
-                let low = seed.get_field(axis).0;
-                let mid = low + 1;
-                let (low_subseed, high_subseed) = seed.split(axis, mid);
-                Tree::Node(
-                    Box::new(gen_tree(rng, low_subseed)),
-                    Box::new(gen_tree(rng, high_subseed))
-                )
-            }
-            else {
-                let filled: bool = rng.random_bool(0.5);
-                if filled {
-                    let dir = random_direction(rng);
-                    Tree::Leaf(GBlock::new(Some(dir), min, max))
-                }
-                else {
-                    Tree::Leaf(GBlock::new(None, min, max))
-                }
-            }
-        }
-        (2, _) => {
-            let axis = widths.iter()
-                .zip(Axis::ALL.iter()).filter(|(w, _)| **w != Width::One)
-                .next().unwrap().1;
-            let (low, high) = seed.get_field(axis);
-            let mid = rng.random_range(low + 1 ..= high - 1);
-            let (low_subseed, high_subseed) = seed.split(axis, mid);
-            Tree::Node(
-                Box::new(gen_tree(rng, low_subseed)),
-                Box::new(gen_tree(rng, high_subseed))
-            )
-        }
-        (1, _) => {
-            let axes: Vec<Axis> = widths.iter()
-                .zip(Axis::ALL.iter()).filter(|(w, _)| **w != Width::One)
-                .map(|x| x.1.clone()).collect();
-            let axis = axes.choose(rng).expect("axis vector should have exactly two elements");
-            let (low, high) = seed.get_field(axis);
-            let mid = rng.random_range(low + 1 ..= high - 1);
-            let (low_subseed, high_subseed) = seed.split(axis, mid);
-            Tree::Node(
-                Box::new(gen_tree(rng, low_subseed)),
-                Box::new(gen_tree(rng, high_subseed))
-            )
-        }
-        (0, _) => {
-            let axis = Axis::ALL.choose(rng).unwrap();
-            let (low, high) = seed.get_field(axis);
+    let widths = [width(xmax - xmin)?, width(ymax - ymin)?, width(zmax - zmin)?];
+    let wide: Vec<Axis> = Axis::ALL.into_iter()
+        .filter(|axis| widths[axis.to_index()] != Width::One)
+        .collect();
+    let length = |axis: &Axis| {
+        let (low, high) = seed.get_field(axis);
+        high - low
+    };
+    match wide.as_slice() {
+        [] => Ok(gen_leaf(rng, params, &seed, None)),
+        // a 1x1xN seed no longer than a block can be is either split up or becomes one long block
+        [axis] if length(axis) <= Block::MAX_LENGTH && !rng.random_bool(params.branch_prob as f64) =>
+            Ok(gen_leaf(rng, params, &seed, Some(axis))),
+        axes => {
+            let axis = axes[rng.random_range(0..axes.len())];
+            let (low, high) = seed.get_field(&axis);
             let mid = rng.random_range(low + 1 ..= high - 1);
-            let (low_subseed, high_subseed) = seed.split(axis, mid);
-            Tree::Node(
-                Box::new(gen_tree(rng, low_subseed)),
-                Box::new(gen_tree(rng, high_subseed))
-            )
-        }
-        _ => panic!("something wrong with the widths of the axes"),
+            let (low_subseed, high_subseed) = seed.split(&axis, mid);
+            Ok(Tree::Node(
+                Box::new(gen_tree(rng, params, low_subseed)?),
+                Box::new(gen_tree(rng, params, high_subseed)?)
+            ))
+        },
     }
 }
 
-pub fn generate_level(side_len: u8) -> Vec<Block> {
-    let len = side_len as i32;
-    let seed = Seed { x: (0, len), y: (0, len), z: (0, len) };
-    let mut rng = rand::rng();
-    let tree = gen_tree(&mut rng, seed);
+const MAX_GENERATION_ATTEMPTS: u32 = 100;
+
+fn generate_level_once<R: Rng>(rng: &mut R, params: &GenParams, dims: IVec3) -> Result<Vec<Block>, GenError> {
+    let seed = Seed { x: (0, dims.x), y: (0, dims.y), z: (0, dims.z) };
+    let tree = gen_tree(rng, params, seed)?;
     let gblocks = flatten_tree(&tree);
     let mut blocks: Vec<Block> = gblocks_to_blocks(gblocks.as_slice());
     remove_locked(&mut blocks);
-    blocks
+    remove_unreachable(&mut blocks);
+    Ok(blocks)
+}
+
+// for debugging remove_locked: the raw generated level with nothing taken out, along with
+// what remove_locked would have removed from it
+pub fn generate_level_keeping_locked(side_len: u8, seed: u64, params: &GenParams) -> Result<(Vec<Block>, Vec<LockedBlock>), GenError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let dims = IVec3::splat(side_len as i32);
+    let tree = gen_tree(&mut rng, params, Seed { x: (0, dims.x), y: (0, dims.y), z: (0, dims.z) })?;
+    let blocks = gblocks_to_blocks(flatten_tree(&tree).as_slice());
+    let locked = remove_locked_reporting(&mut blocks.clone());
+    Ok((blocks, locked))
+}
+
+pub fn random_seed() -> u64 {
+    rand::rng().random()
+}
+
+// a fresh level every call, with the default params
+pub fn generate_level(side_len: u8) -> Result<Vec<Block>, GenError> {
+    generate_level_seeded(side_len, rand::random(), &GenParams::default())
+}
+
+pub fn generate_level_seeded(side_len: u8, seed: u64, params: &GenParams) -> Result<Vec<Block>, GenError> {
+    generate_level_dims_with_params(IVec3::splat(side_len as i32), seed, params)
 }
 
-pub fn locked_blocks_to_remove(blocks: &[Block]) -> Vec<Block> {
-    let mut forward: Vec<Block> = Vec::new();
-    let mut backward: Vec<Block> = Vec::new();
-    for block in blocks.iter() {
-        let seen_positive: bool = !forward.is_empty();
-        let seen_negative: bool = !backward.is_empty();
-        if !seen_negative && block.direction.positive {
-            forward.push(*block);
+// a box of any size rather than a cube, from the given seed or a random one
+pub fn generate_level_dims(dims: IVec3, rng_seed: Option<u64>) -> Result<Vec<Block>, GenError> {
+    generate_level_dims_with_params(dims, rng_seed.unwrap_or_else(random_seed), &GenParams::default())
+}
+
+pub fn generate_level_dims_with_params(dims: IVec3, seed: u64, params: &GenParams) -> Result<Vec<Block>, GenError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    generate_playable(&mut rng, params, dims, MAX_GENERATION_ATTEMPTS)
+}
+
+// the last candidate is never handed out unchecked: running out of attempts is an error
+fn generate_playable<R: Rng>(rng: &mut R, params: &GenParams, dims: IVec3, max_attempts: u32) -> Result<Vec<Block>, GenError> {
+    let mut params = *params;
+    for _ in 0..max_attempts {
+        let blocks = generate_level_once(rng, &params, dims)?;
+        if is_playable(blocks.as_slice()) {
+            return Ok(blocks);
+        }
+        // sparse settings on a small volume can keep leaving nothing behind
+        if blocks.is_empty() {
+            params.fill_prob = (params.fill_prob + 0.1).min(1.0);
+        }
+    }
+    Err(GenError::NoPlayableLevel { attempts: max_attempts })
+}
+
+// new directions for the same shapes, drawn the way the generator draws them, until the level is
+// solvable again. Gives up after MAX_GENERATION_ATTEMPTS and leaves the blocks as they were
+pub fn reassign_directions<R: Rng>(blocks: &mut [Block], rng: &mut R) -> bool {
+    let original: Vec<Direction> = blocks.iter().map(|b| b.direction).collect();
+    for _ in 0..MAX_GENERATION_ATTEMPTS {
+        for block in blocks.iter_mut() {
+            let long_axis = block.get_elongation().map(|(axis, _)| axis);
+            block.direction = biased_direction(rng, long_axis.as_ref());
         }
-        if seen_positive && !block.direction.positive {
-            backward.push(*block);
+        if is_solvable(blocks) {
+            return true;
         }
     }
-    if !forward.is_empty() && !backward.is_empty() {
-        forward.iter().chain(backward.iter()).copied().collect()
+    for (block, direction) in blocks.iter_mut().zip(original) {
+        block.direction = direction;
     }
-    else {
-        Vec::new()
+    false
+}
+
+// an empty level would count as cleared the moment it appears
+fn is_playable(blocks: &[Block]) -> bool {
+    !blocks.is_empty() && validate_no_overlap(blocks).is_ok() && is_solvable(blocks)
+}
+
+// returns the indices of the first pair of blocks sharing a cell
+pub fn validate_no_overlap(blocks: &[Block]) -> Result<(), (usize, usize)> {
+    for (i, b1) in blocks.iter().enumerate() {
+        for (j, b2) in blocks.iter().enumerate().skip(i + 1) {
+            if b1.overlaps(b2) {
+                return Err((i, j));
+            }
+        }
     }
+    Ok(())
+}
+
+/// A block can be removed if clicking it in gameplay would send it flying away.
+pub fn is_removable(block: &Block, index: &BlockIndex) -> bool {
+    let nearest = index.nearest_block_in_front(block);
+    nearest.and_then(|b| block.move_block(&b)).is_none()
 }
 
-pub fn project_vec(v: Vec3, axes: [Axis; 2]) -> Vec2 {
-    Vec2::new(axes[0].vec3_component(v), axes[1].vec3_component(v))
+// indices of the blocks that would fly away if clicked right now
+pub fn free_blocks(blocks: &[Block]) -> Vec<usize> {
+    let index = BlockIndex::new(blocks.iter().copied());
+    (0..blocks.len())
+        .filter(|i| is_removable(&blocks[*i], &index))
+        .collect()
+}
+
+// numbers for tuning GenParams against how hard the generated levels actually are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelStats {
+    pub blocks: usize,
+    // in the order of Direction::ALL
+    pub per_direction: [usize; 6],
+    pub longest_line: usize,
+    // clicks in the shortest solution, None if the solver gave up or there is none
+    pub solver_depth: Option<usize>,
+}
+
+impl std::fmt::Display for LevelStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} blocks (", self.blocks)?;
+        for (i, (direction, count)) in Direction::ALL.iter().zip(self.per_direction).enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(f, "{}{}{:?}: {}", separator, if direction.positive { "+" } else { "-" }, direction.axis, count)?;
+        }
+        write!(f, "), longest line {}, ", self.longest_line)?;
+        match self.solver_depth {
+            Some(depth) => write!(f, "solved in {} clicks", depth),
+            None => write!(f, "no solution found"),
+        }
+    }
+}
+
+// the level is solved within its own bounding box, like a level loaded from a file
+pub fn level_stats(blocks: &[Block]) -> LevelStats {
+    let mut per_direction = [0; 6];
+    for block in blocks {
+        if let Some(i) = Direction::ALL.iter().position(|d| *d == block.direction) {
+            per_direction[i] += 1;
+        }
+    }
+    let lower = blocks.iter().fold(IVec3::MAX, |acc, b| acc.min(b.min));
+    let upper = blocks.iter().fold(IVec3::MIN, |acc, b| acc.max(b.max));
+    LevelStats {
+        blocks: blocks.len(),
+        per_direction,
+        longest_line: BlockIndex::new(blocks.iter().copied()).longest_line(),
+        solver_depth: if blocks.is_empty() { Some(0) } else { solve(blocks, (lower, upper)).map(|s| s.len()) },
+    }
+}
+
+// picks the removable block whose removal frees the most other blocks, preferring the one
+// closest to leaving the level when that's a tie
+pub fn best_hint(blocks: &[Block], (lower, upper): (IVec3, IVec3)) -> Option<usize> {
+    let index = BlockIndex::new(blocks.iter().copied());
+    (0..blocks.len())
+        .filter(|i| is_removable(&blocks[*i], &index))
+        .max_by_key(|i| {
+            let mut rest = index.clone();
+            rest.remove(&blocks[*i]);
+            let freed = blocks.iter().enumerate()
+                .filter(|(j, b)| j != i && is_removable(b, &rest))
+                .count();
+            (freed, -blocks[*i].distance_to_exit(lower, upper))
+        })
+}
+
+// the order in which blocks can be removed to clear the whole board, without any pushes
+pub fn solve_greedy(blocks: &[Block]) -> Option<Vec<Block>> {
+    let mut remaining: Vec<Block> = blocks.to_vec();
+    let mut index = BlockIndex::new(blocks.iter().copied());
+    let mut order: Vec<Block> = Vec::new();
+    while !remaining.is_empty() {
+        let free = remaining.iter()
+            .position(|b| is_removable(b, &index))?;
+        let removed = remaining.swap_remove(free);
+        index.remove(&removed);
+        order.push(removed);
+    }
+    Some(order)
+}
+
+// the board after clicking blocks[i], following the same rules as gameplay: a block with
+// others in front pushes the whole line up to the level bounds, a free block flies away.
+// None if the click changes nothing
+pub fn click(blocks: &[Block], i: usize, (lower, upper): (IVec3, IVec3)) -> Option<Vec<Block>> {
+    let block = blocks[i];
+    let index = BlockIndex::new(blocks.iter().copied());
+    let in_front = index.blocks_in_front(&block);
+    let axis = block.direction.axis;
+    let bound = if block.direction.positive { axis.ivec3_component(upper) } else { axis.ivec3_component(lower) };
+    let pushed = if in_front.is_empty() { None } else { block.push_line(blocks, bound) };
+    let mut board = blocks.to_vec();
+    match pushed {
+        Some(moves) => {
+            for (old, new) in moves {
+                if let Some(b) = board.iter_mut().find(|b| **b == old) {
+                    *b = new;
+                }
+            }
+        },
+        None => match index.nearest_block_in_front(&block).and_then(|b| block.move_block(&b)) {
+            Some(moved) if moved == block => return None,
+            Some(moved) => board[i] = moved,
+            None => {
+                board.remove(i);
+            },
+        },
+    }
+    Some(board)
+}
+
+// boards explored before solve gives up on finding the shortest sequence
+pub const SOLVE_STATE_LIMIT: usize = 20_000;
+
+// the shortest sequence of clicks that clears the board, as the blocks to click in order.
+// Every click removes at most one block, so a push-free greedy solution is already optimal;
+// only boards that need pushes are searched breadth-first, up to SOLVE_STATE_LIMIT boards
+pub fn solve(blocks: &[Block], bounds: (IVec3, IVec3)) -> Option<Vec<Block>> {
+    if let Some(order) = solve_greedy(blocks) {
+        return Some(order);
+    }
+    // every explored board, with the board and click that led to it
+    let mut came_from: HashMap<u64, Option<(u64, Block)>> = HashMap::new();
+    came_from.insert(board_hash(blocks), None);
+    let mut queue: VecDeque<Vec<Block>> = VecDeque::from([blocks.to_vec()]);
+    while let Some(board) = queue.pop_front() {
+        let key = board_hash(&board);
+        for i in 0..board.len() {
+            let Some(next) = click(&board, i, bounds) else { continue };
+            let next_key = board_hash(&next);
+            if came_from.contains_key(&next_key) {
+                continue;
+            }
+            came_from.insert(next_key, Some((key, board[i])));
+            if next.is_empty() {
+                let mut order = Vec::new();
+                let mut current = next_key;
+                while let Some(Some((previous, clicked))) = came_from.get(&current) {
+                    order.push(*clicked);
+                    current = *previous;
+                }
+                order.reverse();
+                return Some(order);
+            }
+            if came_from.len() >= SOLVE_STATE_LIMIT {
+                return None;
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+pub fn is_solvable(blocks: &[Block]) -> bool {
+    solve_greedy(blocks).is_some()
+}
+
+// blocks on a line along `axis` that can never leave it: blocks pointing along the line stay on
+// it and can't pass each other, so every block pointing forward with one pointing backward
+// somewhere ahead of it is stuck, and so is every backward one with a forward one behind it.
+// Blocks pointing off the line can leave sideways and don't lock anything
+pub fn locked_blocks_to_remove(axis: &Axis, blocks: &[Block]) -> Vec<Block> {
+    let mut line: Vec<Block> = blocks.iter()
+        .filter(|b| b.direction.axis == *axis)
+        .copied()
+        .collect();
+    line.sort_by_key(|b| axis.ivec3_component(b.min));
+    let forward = Direction::new(*axis, true);
+    let first_forward = line.iter().position(|b| b.direction == forward);
+    let last_backward = line.iter().rposition(|b| b.direction.is_opposite(&forward));
+    match (first_forward, last_backward) {
+        (Some(first), Some(last)) if first < last => line[first..=last].to_vec(),
+        _ => Vec::new(),
+    }
 }
 
 pub fn project_ivec(v: IVec3, axes: [Axis; 2]) -> IVec2 {
     IVec2::new(axes[0].ivec3_component(v), axes[1].ivec3_component(v))
 }
 
-pub fn extract_along_line(dir: &Axis, point: Vec2, blocks: &[Block]) -> Vec<Block> {
+// blocks crossing the line along `dir` through `cell`, decided on the integer corners. A block
+// one cell thick across the line ends up on exactly one line, wider ones on every line they cover
+pub fn extract_cell_line(dir: &Axis, cell: IVec2, blocks: &[Block]) -> Vec<Block> {
     let other = dir.remaining_two();
     blocks.iter()
-        .filter(|b: &&Block| {
-            let proj = project_vec(b.get_center(), other);
-            let manhattan_dist = (proj - point).abs().element_sum();
-            manhattan_dist <= 0.5
+        .filter(|b| {
+            let (min, max) = (project_ivec(b.min, other), project_ivec(b.max, other));
+            cell.cmpge(min).all() && cell.cmplt(max).all()
         })
         .copied()
         .collect()
 }
 
+// a block taken out by remove_locked, with the line it was caught on: the line runs along
+// `axis` through cell `line` of the plane spanned by the other two axes
+#[derive(Debug, Clone, Copy)]
+pub struct LockedBlock {
+    pub block: Block,
+    pub axis: Axis,
+    pub line: IVec2,
+}
+
 pub fn remove_locked(blocks: &mut Vec<Block>) {
+    remove_locked_reporting(blocks);
+}
+
+pub fn remove_locked_reporting(blocks: &mut Vec<Block>) -> Vec<LockedBlock> {
     let lower = blocks.iter().fold(IVec3::MAX, |acc, v| acc.min(v.min));
     let upper = blocks.iter().fold(IVec3::MIN, |acc, v| acc.max(v.max));
+    let mut removed = Vec::new();
     for axis in Axis::ALL.iter() {
         let remaining = axis.remaining_two();
         let lower_proj = project_ivec(lower, remaining);
         let upper_proj = project_ivec(upper, remaining);
         for x in lower_proj.x..upper_proj.x {
             for y in lower_proj.y..upper_proj.y {
-                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
-                let line_of_blocks = extract_along_line(axis, p, blocks.as_slice());
-                let to_remove = locked_blocks_to_remove(line_of_blocks.as_slice());
+                let line_of_blocks = extract_cell_line(axis, IVec2::new(x, y), blocks.as_slice());
+                let to_remove = locked_blocks_to_remove(axis, line_of_blocks.as_slice());
                 blocks.retain(|b| !to_remove.contains(b));
+                removed.extend(to_remove.into_iter().map(|block| LockedBlock { block, axis: *axis, line: IVec2::new(x, y) }));
+            }
+        }
+    }
+    removed
+}
+
+// remove_locked only catches blocks facing each other on a single line. Anything still stuck
+// is found by playing the level greedily: whenever nothing is free, the stuck block that sits
+// in front of the most other stuck blocks is dropped from the level, and play continues.
+// Taking blocks away only frees the others, so the greedy solver can then clear what is left;
+// the playability check in generate_level_dims_with_params still has the final say
+pub fn remove_unreachable(blocks: &mut Vec<Block>) {
+    let mut remaining: Vec<Block> = blocks.clone();
+    let mut index = BlockIndex::new(blocks.iter().copied());
+    while !remaining.is_empty() {
+        let free = remaining.iter().position(|b| is_removable(b, &index));
+        let next = free.unwrap_or_else(|| {
+            (0..remaining.len())
+                .max_by_key(|i| {
+                    remaining.iter()
+                        .filter(|b| index.nearest_block_in_front(b) == Some(remaining[*i]))
+                        .count()
+                })
+                .expect("remaining is not empty")
+        });
+        let removed = remaining.swap_remove(next);
+        index.remove(&removed);
+        if free.is_none() {
+            blocks.retain(|b| *b != removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_params() -> GenParams {
+        GenParams { fill_prob: 0.0, ..GenParams::default() }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_level() {
+        let params = GenParams::default();
+        assert_eq!(generate_level_seeded(4, 42, &params), generate_level_seeded(4, 42, &params));
+    }
+
+    #[test]
+    fn unseeded_levels_are_playable() {
+        for _ in 0..10 {
+            assert!(is_playable(&generate_level(3).unwrap()));
+        }
+    }
+
+    fn cube_seed(side_len: i32) -> Seed {
+        Seed { x: (0, side_len), y: (0, side_len), z: (0, side_len) }
+    }
+
+    // over a fixed set of seeds, so the comparisons don't flake
+    fn average_leaves(params: &GenParams, side_len: i32) -> f32 {
+        let total: usize = (0..50).map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            flatten_tree(&gen_tree(&mut rng, params, cube_seed(side_len)).unwrap()).len()
+        }).sum();
+        total as f32 / 50.0
+    }
+
+    #[test]
+    fn more_branching_gives_more_blocks() {
+        let few = average_leaves(&GenParams { branch_prob: 0.1, ..GenParams::default() }, 4);
+        let many = average_leaves(&GenParams { branch_prob: 0.9, ..GenParams::default() }, 4);
+        assert!(many > few, "{} blocks on average with more branching, {} with less", many, few);
+    }
+
+    #[test]
+    fn box_levels_stay_inside_their_dims() {
+        let dims = IVec3::new(5, 3, 4);
+        for seed in 0..20 {
+            let blocks = generate_level_dims(dims, Some(seed)).unwrap();
+            assert!(blocks.iter().all(|b| b.min.cmpge(IVec3::ZERO).all() && b.max.cmple(dims).all()));
+            assert_eq!(validate_no_overlap(&blocks), Ok(()));
+        }
+    }
+
+    #[test]
+    fn locked_blocks_are_found_past_the_shorter_sides_of_a_box() {
+        // the locked pair sits at x = 4, outside the 0..3 range of y, so it is only caught if
+        // each axis is scanned over its own extent
+        let facing = [
+            Block::new(Direction::ZP, IVec3::new(4, 2, 0), IVec3::new(5, 3, 1)),
+            Block::new(Direction::ZN, IVec3::new(4, 2, 3), IVec3::new(5, 3, 4)),
+        ];
+        let mut blocks = vec![Block::new(Direction::XN, IVec3::ZERO, IVec3::ONE), facing[0], facing[1]];
+        let removed = remove_locked_reporting(&mut blocks);
+        assert_eq!(blocks, vec![Block::new(Direction::XN, IVec3::ZERO, IVec3::ONE)]);
+        assert_eq!(removed.len(), 2);
+        for locked in removed {
+            assert!(facing.contains(&locked.block));
+            assert_eq!((locked.axis, locked.line), (Axis::Z, IVec2::new(4, 2)));
+        }
+    }
+
+    #[test]
+    fn generator_makes_blocks_of_every_length() {
+        let params = GenParams { branch_prob: 0.2, fill_prob: 1.0, align_prob: 0.0 };
+        let mut lengths = Vec::new();
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let blocks = gblocks_to_blocks(&flatten_tree(&gen_tree(&mut rng, &params, cube_seed(3)).unwrap()));
+            for b in blocks {
+                let size = b.get_isize();
+                match b.get_elongation() {
+                    Some((axis, length)) => {
+                        assert_eq!(axis.ivec3_component(size), length);
+                        assert_eq!(size.element_sum(), length + 2);
+                        lengths.push(length);
+                    },
+                    None => assert_eq!(size, IVec3::ONE),
+                }
+            }
+        }
+        assert!(lengths.contains(&2));
+        assert!(lengths.contains(&Block::MAX_LENGTH));
+    }
+
+    #[test]
+    fn overlap_check_names_the_first_pair() {
+        let cube = |min: IVec3| Block::new(Direction::XP, min, min + IVec3::ONE);
+        let long = Block::new(Direction::YP, IVec3::new(2, 0, 0), IVec3::new(2, 2, 0) + IVec3::ONE);
+        assert_eq!(validate_no_overlap(&[cube(IVec3::ZERO), cube(IVec3::X), long]), Ok(()));
+        assert_eq!(validate_no_overlap(&[cube(IVec3::ZERO), long, cube(IVec3::new(2, 1, 0))]), Err((1, 2)));
+    }
+
+    #[test]
+    fn generated_levels_never_overlap() {
+        for side_len in 3..=8 {
+            for seed in 0..5 {
+                let blocks = generate_level_seeded(side_len, seed, &GenParams::default()).unwrap();
+                assert_eq!(validate_no_overlap(&blocks), Ok(()), "side {} seed {}", side_len, seed);
             }
         }
     }
+
+    // a timing comparison rather than a check, see the printed numbers with
+    // cargo test --release --no-default-features -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn time_front_lookups_and_solve_on_a_large_board() {
+        let blocks = generate_level_seeded(10, 0, &GenParams::default()).unwrap();
+        let index = BlockIndex::new(blocks.iter().copied());
+        // ties between blocks equally far ahead can go either way, where the block stops can't
+        let stop = |b: &Block, nearest: Option<Block>| nearest.and_then(|n| b.move_block(&n));
+        let start = std::time::Instant::now();
+        let scanned: Vec<_> = blocks.iter().map(|b| stop(b, b.get_nearest_block_in_front(blocks.iter().copied()))).collect();
+        let scan_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let indexed: Vec<_> = blocks.iter().map(|b| stop(b, index.nearest_block_in_front(b))).collect();
+        let index_time = start.elapsed();
+        assert_eq!(scanned, indexed);
+        let start = std::time::Instant::now();
+        let solution = solve(&blocks, (IVec3::ZERO, IVec3::splat(10)));
+        let solve_time = start.elapsed();
+        assert!(solution.is_some());
+        println!("{} blocks: scan {:?}, index {:?}, solve {:?}", blocks.len(), scan_time, index_time, solve_time);
+    }
+
+    #[test]
+    fn generated_levels_are_cleared_by_greedy_play() {
+        for difficulty in Difficulty::ALL {
+            for seed in 0..30 {
+                let blocks = generate_level_seeded(4, seed, &difficulty.params()).unwrap();
+                assert_eq!(validate_no_overlap(&blocks), Ok(()), "{:?} seed {}", difficulty, seed);
+                assert!(is_solvable(&blocks), "{:?} seed {}", difficulty, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn remove_unreachable_leaves_a_greedily_clearable_board() {
+        for seed in 0..30 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let params = GenParams { fill_prob: 0.9, ..GenParams::default() };
+            let mut blocks = gblocks_to_blocks(&flatten_tree(&gen_tree(&mut rng, &params, cube_seed(4)).unwrap()));
+            remove_locked(&mut blocks);
+            remove_unreachable(&mut blocks);
+            assert!(is_solvable(&blocks), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn rotating_a_level_keeps_it_solvable() {
+        for seed in 0..20 {
+            let blocks = generate_level_seeded(4, seed, &GenParams::default()).unwrap();
+            for axis in Axis::ALL {
+                let rotated: Vec<Block> = blocks.iter().map(|b| b.rotate_around(axis)).collect();
+                assert_eq!(is_solvable(&rotated), is_solvable(&blocks), "seed {} around {:?}", seed, axis);
+                assert_eq!(validate_no_overlap(&rotated), Ok(()));
+                let full_turn: Vec<Block> = (0..4).fold(blocks.clone(), |level, _| level.iter().map(|b| b.rotate_around(axis)).collect());
+                assert_eq!(full_turn, blocks);
+            }
+        }
+    }
+
+    #[test]
+    fn mirroring_twice_gives_the_level_back_and_keeps_it_solvable() {
+        for seed in 0..20 {
+            let blocks = generate_level_seeded(4, seed, &GenParams::default()).unwrap();
+            for axis in Axis::ALL {
+                let mirrored: Vec<Block> = blocks.iter().map(|b| b.mirror(axis)).collect();
+                assert_eq!(is_solvable(&mirrored), is_solvable(&blocks), "seed {} along {:?}", seed, axis);
+                assert_eq!(validate_no_overlap(&mirrored), Ok(()));
+                let back: Vec<Block> = mirrored.iter().map(|b| b.mirror(axis)).collect();
+                assert_eq!(back, blocks);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_seeds_are_errors_not_panics() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let flat = Seed { x: (0, 3), y: (0, 0), z: (0, 3) };
+        assert!(matches!(gen_tree(&mut rng, &GenParams::default(), flat), Err(GenError::BadWidth(0))));
+        let inverted = Seed { x: (2, 0), y: (0, 1), z: (0, 1) };
+        assert!(matches!(gen_tree(&mut rng, &GenParams::default(), inverted), Err(GenError::BadWidth(-2))));
+        for seed in [0, u64::MAX] {
+            assert_eq!(generate_level_seeded(0, seed, &GenParams::default()), Err(GenError::BadWidth(0)));
+            assert_eq!(generate_level_dims(IVec3::new(3, 0, 2), Some(seed)), Err(GenError::BadWidth(0)));
+        }
+    }
+
+    #[test]
+    fn extreme_seeds_and_the_smallest_width_still_give_levels() {
+        for seed in [0, u64::MAX] {
+            for side_len in 1..=3 {
+                let blocks = generate_level_seeded(side_len, seed, &GenParams::default()).unwrap();
+                assert!(is_playable(&blocks), "side {} seed {}", side_len, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_levels_are_never_empty() {
+        let sparse = GenParams { fill_prob: 0.05, ..GenParams::default() };
+        for side_len in 1..=6 {
+            for seed in 0..20 {
+                for params in [sparse, GenParams::default()] {
+                    let blocks = generate_level_seeded(side_len, seed, &params).unwrap();
+                    assert!(!blocks.is_empty(), "side {} seed {} {:?}", side_len, seed, params);
+                }
+            }
+        }
+    }
+
+    // cubes along Z at x = y = 0, in the order given
+    fn z_line(directions: &[Direction]) -> Vec<Block> {
+        directions.iter().enumerate()
+            .map(|(z, d)| Block::new(*d, IVec3::new(0, 0, 2 * z as i32), IVec3::new(1, 1, 2 * z as i32 + 1)))
+            .collect()
+    }
+
+    #[test]
+    fn facing_pair_is_locked() {
+        let line = z_line(&[Direction::ZP, Direction::ZN]);
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &line), line);
+    }
+
+    #[test]
+    fn blocks_heading_the_same_way_are_not_locked() {
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &z_line(&[Direction::ZP, Direction::ZP])), Vec::new());
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &z_line(&[Direction::ZN, Direction::ZN])), Vec::new());
+    }
+
+    #[test]
+    fn blocks_heading_apart_are_not_locked() {
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &z_line(&[Direction::ZN, Direction::ZP])), Vec::new());
+    }
+
+    #[test]
+    fn only_the_facing_part_of_a_longer_line_is_locked() {
+        let line = z_line(&[Direction::ZP, Direction::ZN, Direction::ZP]);
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &line), line[..2].to_vec());
+        let line = z_line(&[Direction::ZN, Direction::ZP, Direction::XP, Direction::ZP, Direction::ZN, Direction::ZN]);
+        assert_eq!(locked_blocks_to_remove(&Axis::Z, &line), vec![line[1], line[3], line[4], line[5]]);
+    }
+
+    #[test]
+    fn lines_are_found_regardless_of_input_order() {
+        let mut line = z_line(&[Direction::ZP, Direction::YP, Direction::ZN]);
+        line.reverse();
+        let locked = locked_blocks_to_remove(&Axis::Z, &line);
+        assert_eq!(locked, vec![line[2], line[0]]);
+    }
+
+    #[test]
+    fn every_block_is_on_one_line_per_cell_of_its_cross_section() {
+        // negative corners and long blocks put centers on half cells, where float matching slipped
+        let blocks = [
+            Block::new(Direction::ZP, IVec3::new(-1, -1, 0), IVec3::new(0, 0, 1)),
+            Block::new(Direction::XP, IVec3::new(0, -1, 0), IVec3::new(2, 0, 1)),
+            Block::new(Direction::YN, IVec3::new(-1, 0, -2), IVec3::new(0, 3, -1)),
+            Block::new(Direction::ZN, IVec3::new(1, 1, -3), IVec3::new(2, 2, 0)),
+        ];
+        for axis in Axis::ALL {
+            let other = axis.remaining_two();
+            for b in blocks {
+                let lines = (-4..4)
+                    .flat_map(|i| (-4..4).map(move |j| IVec2::new(i, j)))
+                    .filter(|cell| extract_cell_line(&axis, *cell, &blocks).contains(&b))
+                    .count();
+                let cross_section = project_ivec(b.get_isize(), other);
+                assert_eq!(lines as i32, cross_section.x * cross_section.y, "{:?} along {:?}", b, axis);
+            }
+        }
+    }
+
+    #[test]
+    fn reassigning_directions_keeps_the_shapes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (mut changed, mut total) = (0, 0);
+        for seed in 0..10 {
+            let original = generate_level_seeded(4, seed, &GenParams::default()).unwrap();
+            let mut blocks = original.clone();
+            assert!(reassign_directions(&mut blocks, &mut rng));
+            let shapes = |level: &[Block]| level.iter().map(|b| (b.min, b.max, b.step)).collect::<Vec<_>>();
+            assert_eq!(shapes(&blocks), shapes(&original));
+            assert!(is_solvable(&blocks));
+            changed += blocks.iter().zip(&original).filter(|(new, old)| new.direction != old.direction).count();
+            total += blocks.len();
+        }
+        // a block can draw its old direction again, but most shouldn't
+        assert!(changed * 2 > total, "{} of {} directions changed", changed, total);
+    }
+
+    // blocks the generator fills in before anything is pruned, averaged over a fixed set of seeds
+    fn average_filled(params: &GenParams, side_len: i32) -> f32 {
+        let total: usize = (0..50).map(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            gblocks_to_blocks(&flatten_tree(&gen_tree(&mut rng, params, cube_seed(side_len)).unwrap())).len()
+        }).sum();
+        total as f32 / 50.0
+    }
+
+    #[test]
+    fn block_count_grows_with_the_level() {
+        let counts: Vec<f32> = [1, 8, 15].into_iter()
+            .map(|level| average_filled(&difficulty_params(level), 4))
+            .collect();
+        assert!(counts.windows(2).all(|w| w[0] < w[1]), "{:?}", counts);
+    }
+
+    #[test]
+    fn difficulty_params_ramp_the_fill_up_to_the_cap() {
+        assert_eq!(difficulty_params(1).fill_prob, GenParams::default().fill_prob);
+        assert!(difficulty_params(5).fill_prob > difficulty_params(1).fill_prob);
+        assert_eq!(difficulty_params(u8::MAX).fill_prob, MAX_FILL_PROB);
+    }
+
+    #[test]
+    fn running_out_of_attempts_is_an_error() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let result = generate_playable(&mut rng, &empty_params(), IVec3::splat(3), 1);
+        assert_eq!(result, Err(GenError::NoPlayableLevel { attempts: 1 }));
+    }
+
+    #[test]
+    fn empty_attempts_raise_the_fill_until_something_is_left() {
+        let blocks = generate_level_dims_with_params(IVec3::splat(3), 0, &empty_params()).unwrap();
+        assert!(is_playable(&blocks));
+    }
 }
@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use crate::lighting::KeyLight;
+use crate::settings::Settings;
+use crate::{BlockSceneMarker, LevelBounds, LevelCenter};
+
+#[derive(Component)]
+pub struct GroundPlane;
+
+// how far below the lowest block the plane sits, and how much wider than the level it is
+const GROUND_GAP: f32 = 1.0;
+const GROUND_SCALE: f32 = 4.0;
+
+// follows the level like the camera framing does, so rotated or transformed boards still get
+// their shadow directly below them
+pub fn place_ground_plane(
+    mut commands: Commands,
+    level_bounds: Res<LevelBounds>,
+    level_center: Res<LevelCenter>,
+    settings: Res<Settings>,
+    grounds: Query<Entity, With<GroundPlane>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    grounds.iter().for_each(|e| commands.entity(e).despawn());
+    let LevelBounds(lower, upper) = *level_bounds;
+    let (lower, upper) = (lower.as_vec3(), upper.as_vec3());
+    let side = (upper - lower).max_element() * GROUND_SCALE;
+    let below = Vec3::new(lower.midpoint(upper).x, lower.y - GROUND_GAP, lower.midpoint(upper).z);
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(side, side))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgb(0.3, 0.3, 0.35),
+            perceptual_roughness: 1.0,
+            ..default()
+        })),
+        Transform::from_translation(below - level_center.0),
+        if settings.show_ground { Visibility::Inherited } else { Visibility::Hidden },
+        Pickable::IGNORE,
+        GroundPlane,
+        BlockSceneMarker,
+    ));
+}
+
+// the floating look has neither the plane nor the shadows
+pub fn apply_ground_setting(
+    settings: Res<Settings>,
+    mut grounds: Query<&mut Visibility, With<GroundPlane>>,
+    mut lights: Query<&mut DirectionalLight, With<KeyLight>>,
+) {
+    for mut visibility in grounds.iter_mut() {
+        visibility.set_if_neq(if settings.show_ground { Visibility::Inherited } else { Visibility::Hidden });
+    }
+    for mut light in lights.iter_mut() {
+        light.shadows_enabled = settings.show_ground;
+    }
+}
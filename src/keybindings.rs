@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::save;
+
+// the single-key shortcuts that can be rebound. Keys that come in groups (the view keys 1-6,
+// arrows, PageUp/PageDown) and the debug keys stay fixed, see RESERVED
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Undo,
+    Reset,
+    Hint,
+    AutoSolve,
+    Pause,
+    CameraHome,
+    Reframe,
+    Orthographic,
+    Reshuffle,
+    Rotate,
+    Mirror,
+    Export,
+    Replay,
+    ToggleBounds,
+    ToggleDirections,
+    BlockInfo,
+    Cutaway,
+    CutawayAxis,
+}
+
+impl Action {
+    pub const ALL: [Self; 18] = [
+        Self::Undo, Self::Reset, Self::Hint, Self::AutoSolve, Self::Pause,
+        Self::CameraHome, Self::Reframe, Self::Orthographic,
+        Self::Reshuffle, Self::Rotate, Self::Mirror, Self::Export, Self::Replay,
+        Self::ToggleBounds, Self::ToggleDirections, Self::BlockInfo, Self::Cutaway, Self::CutawayAxis,
+    ];
+
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Undo => "Undo",
+            Self::Reset => "Reset level",
+            Self::Hint => "Hint",
+            Self::AutoSolve => "Auto-solve (hold)",
+            Self::Pause => "Pause",
+            Self::CameraHome => "Camera home",
+            Self::Reframe => "Frame level",
+            Self::Orthographic => "Orthographic view",
+            Self::Reshuffle => "Reshuffle directions",
+            Self::Rotate => "Rotate board",
+            Self::Mirror => "Mirror board",
+            Self::Export => "Export level",
+            Self::Replay => "Replay (Shift saves)",
+            Self::ToggleBounds => "Show bounds",
+            Self::ToggleDirections => "Show directions",
+            Self::BlockInfo => "Block info",
+            Self::Cutaway => "Cutaway",
+            Self::CutawayAxis => "Cutaway axis",
+        }
+    }
+
+    pub fn default_key(self: &Self) -> KeyCode {
+        match self {
+            Self::Undo => KeyCode::KeyU,
+            Self::Reset => KeyCode::KeyR,
+            Self::Hint => KeyCode::KeyH,
+            Self::AutoSolve => KeyCode::KeyN,
+            Self::Pause => KeyCode::Escape,
+            Self::CameraHome => KeyCode::Space,
+            Self::Reframe => KeyCode::KeyF,
+            Self::Orthographic => KeyCode::KeyO,
+            Self::Reshuffle => KeyCode::KeyQ,
+            Self::Rotate => KeyCode::KeyT,
+            Self::Mirror => KeyCode::KeyM,
+            Self::Export => KeyCode::KeyX,
+            Self::Replay => KeyCode::KeyP,
+            Self::ToggleBounds => KeyCode::KeyB,
+            Self::ToggleDirections => KeyCode::KeyG,
+            Self::BlockInfo => KeyCode::KeyI,
+            Self::Cutaway => KeyCode::KeyC,
+            Self::CutawayAxis => KeyCode::KeyV,
+        }
+    }
+}
+
+// keys read directly by other systems. Binding an action to one of these would make a single
+// press do two things, so bind refuses them
+pub const RESERVED: [KeyCode; 25] = [
+    // camera views
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+    // block selection
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD,
+    KeyCode::Enter, KeyCode::NumpadEnter,
+    // cutaway depth
+    KeyCode::PageUp, KeyCode::PageDown,
+    // fps counter, debug overlay, stats panel
+    KeyCode::F3, KeyCode::F9, KeyCode::F10,
+    // modifiers for axis picking and saving replays
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+];
+
+// only the keys changed from their defaults need to be in the file; anything missing, such as
+// actions added later, uses the default
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings(HashMap<Action, KeyCode>);
+
+impl KeyBindings {
+    pub fn key(self: &Self, action: Action) -> KeyCode {
+        self.0.get(&action).copied().unwrap_or(action.default_key())
+    }
+
+    pub fn just_pressed(self: &Self, keys: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keys.just_pressed(self.key(action))
+    }
+
+    pub fn pressed(self: &Self, keys: &ButtonInput<KeyCode>, action: Action) -> bool {
+        keys.pressed(self.key(action))
+    }
+
+    // an action taking a key that's already in use hands its old key to the other action, so
+    // no two actions ever share one. Returns false, leaving the bindings alone, for a reserved key
+    pub fn bind(self: &mut Self, action: Action, key: KeyCode) -> bool {
+        if RESERVED.contains(&key) {
+            return false;
+        }
+        let old = self.key(action);
+        if let Some(other) = Action::ALL.into_iter().find(|a| *a != action && self.key(*a) == key) {
+            self.0.insert(other, old);
+        }
+        self.0.insert(action, key);
+        true
+    }
+}
+
+// KeyU reads better as U
+pub fn key_label(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(letter) => letter.to_string(),
+        None => name,
+    }
+}
+
+// kept next to save.json but in a file of their own, like settings.json: save.json is progress,
+// rewritten from the game state on every clear, while bindings only change on the settings
+// screen. A hand-edited file could still bind a reserved key; those actions go back to their
+// defaults
+pub fn load() -> KeyBindings {
+    let mut bindings: KeyBindings = save::read_json(save::config_path("keys.json"));
+    bindings.0.retain(|_, key| !RESERVED.contains(key));
+    bindings
+}
+
+pub fn store(bindings: &KeyBindings) {
+    save::write_json(save::config_path("keys.json"), bindings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_not_reserved() {
+        for action in Action::ALL {
+            assert!(!RESERVED.contains(&action.default_key()), "{:?}", action);
+        }
+    }
+
+    #[test]
+    fn reserved_keys_cannot_be_bound() {
+        let mut bindings = KeyBindings::default();
+        for key in RESERVED {
+            assert!(!bindings.bind(Action::Undo, key), "{:?}", key);
+            assert_eq!(bindings.key(Action::Undo), Action::Undo.default_key());
+        }
+    }
+
+    #[test]
+    fn binding_a_used_key_swaps() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.bind(Action::Undo, KeyCode::KeyZ));
+        assert!(bindings.bind(Action::Reset, KeyCode::KeyZ));
+        assert_eq!(bindings.key(Action::Reset), KeyCode::KeyZ);
+        assert_eq!(bindings.key(Action::Undo), KeyCode::KeyR);
+        // no key ends up on two actions
+        let mut keys: Vec<KeyCode> = Action::ALL.into_iter().map(|a| bindings.key(a)).collect();
+        keys.sort_by_key(|k| format!("{:?}", k));
+        keys.dedup();
+        assert_eq!(keys.len(), Action::ALL.len());
+    }
+
+    #[test]
+    fn binding_a_free_key_moves_only_that_action() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.bind(Action::Hint, KeyCode::KeyJ));
+        for action in Action::ALL {
+            let expected = if action == Action::Hint { KeyCode::KeyJ } else { action.default_key() };
+            assert_eq!(bindings.key(action), expected, "{:?}", action);
+        }
+    }
+}
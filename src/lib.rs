@@ -0,0 +1,5 @@
+// the engine-independent core of the game: block geometry, level generation and the headless
+// simulator. The Bevy component impls only come in with the `engine` feature
+pub mod block;
+pub mod generation;
+pub mod sim;
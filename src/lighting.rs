@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use crate::settings::Settings;
+use crate::BlockSceneMarker;
+
+// the light that casts shadows, from above and in front like the camera's starting view
+#[derive(Component)]
+pub struct KeyLight;
+
+// a weaker light from the opposite side, so faces turned away from the key light still show
+// their shading instead of going flat
+#[derive(Component)]
+pub struct FillLight;
+
+const KEY_ILLUMINANCE: f32 = light_consts::lux::AMBIENT_DAYLIGHT;
+const FILL_ILLUMINANCE: f32 = KEY_ILLUMINANCE * 0.35;
+
+// directional lights and ambient light don't fall off with distance, so the rig looks the same
+// however far the camera is framed out for bigger levels
+pub fn spawn_light_rig(commands: &mut Commands, settings: &Settings) {
+    commands.spawn((
+        DirectionalLight {
+            illuminance: KEY_ILLUMINANCE,
+            shadows_enabled: settings.show_ground,
+            ..default()
+        },
+        Transform::from_xyz(3.0, 3.0, 3.0).looking_at(Vec3::ZERO, Vec3::Y),
+        KeyLight,
+        BlockSceneMarker,
+    ));
+    commands.spawn((
+        DirectionalLight {
+            illuminance: FILL_ILLUMINANCE,
+            shadows_enabled: false,
+            ..default()
+        },
+        Transform::from_xyz(-3.0, -1.0, -2.0).looking_at(Vec3::ZERO, Vec3::Y),
+        FillLight,
+        BlockSceneMarker,
+    ));
+    commands.insert_resource(ambient_light(settings));
+}
+
+fn ambient_light(settings: &Settings) -> AmbientLight {
+    AmbientLight {
+        color: Color::WHITE,
+        brightness: settings.ambient_brightness,
+        ..default()
+    }
+}
+
+pub fn apply_ambient_setting(mut commands: Commands, settings: Res<Settings>) {
+    commands.insert_resource(ambient_light(&settings));
+}
@@ -1,18 +1,107 @@
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 use bevy::prelude::*;
-mod block;
-mod generation;
+use bevy::asset::{AssetLoader, LoadContext, LoadState, io::Reader};
+use bevy::ecs::spawn::SpawnIter;
+use bevy::scene::SceneInstanceReady;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future};
+use std::collections::{HashMap, HashSet};
+use clear_cube::{block, generation, sim};
+mod achievements;
+mod audio;
+mod block_info;
+mod camera;
+mod cutaway;
+mod daily;
+mod decal;
+mod drag;
+#[cfg(feature = "dev")]
+mod dev;
+mod editor;
+#[cfg(feature = "dev")]
+mod fps_overlay;
+mod gamepad;
+mod ground;
+mod keybindings;
+mod lighting;
+mod minimap;
+mod overlay;
+mod popup;
+mod replay;
+mod save;
+mod score;
+mod seed_entry;
+mod selection;
+mod settings;
+#[cfg(feature = "dev")]
+mod stats_panel;
+mod tutorial;
 
 #[derive(Resource)]
 pub struct BlockModels {
     pub small_model: Handle<Scene>,
     pub wide_model: Handle<Scene>,
-    pub long_model: Handle<Scene>
+    pub long_model: Handle<Scene>,
+    pub very_wide_model: Handle<Scene>,
+    pub very_long_model: Handle<Scene>,
 }
 
-#[derive(serde::Deserialize, Asset, TypePath, Resource)]
+#[derive(serde::Serialize, serde::Deserialize, Asset, TypePath, Resource, PartialEq, Debug)]
 pub struct Level(Vec<block::Block>);
 
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Level, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+}
+
+#[derive(Resource)]
+pub struct LevelHandle(Handle<Level>);
+
+// everything that can be wrong with a hand-authored level, blocks are referred to by index
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+    Empty,
+    NoExtent { index: usize, min: IVec3, max: IVec3 },
+    UnsupportedShape { index: usize, size: IVec3 },
+    OutOfBounds { index: usize, min: IVec3, max: IVec3 },
+    Overlap { first: usize, second: usize },
+    BadStep { index: usize, step: i32 },
+}
+
+impl std::fmt::Display for LevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "level has no blocks"),
+            Self::NoExtent { index, min, max } =>
+                write!(f, "block {} has no extent: min {} is not below max {} on every axis", index, min, max),
+            Self::UnsupportedShape { index, size } =>
+                write!(f, "block {} has size {}, only 1x1x1 or 1x1xN up to {} long is supported", index, size, block::Block::MAX_LENGTH),
+            Self::OutOfBounds { index, min, max } =>
+                write!(f, "block {} spans {}..{}, outside of -{limit}..{limit}", index, min, max, limit = Level::MAX_COORD),
+            Self::Overlap { first, second } => write!(f, "blocks {} and {} overlap", first, second),
+            Self::BadStep { index, step } => write!(f, "block {} moves {} cells at a time, it needs to move at least 1", index, step),
+        }
+    }
+}
+
 impl Level {
     pub fn bounds(self: &Self) -> (Vec3, Vec3) {
         let lower = self.0.iter().fold(Vec3::MAX, |acc, v| acc.min(v.min.as_vec3()));
@@ -24,205 +113,1628 @@ impl Level {
         let (lower, upper) = self.bounds();
         lower.midpoint(upper)
     }
+
+    // every block has to lie within -MAX_COORD..MAX_COORD on each axis
+    pub const MAX_COORD: i32 = 64;
+
+    pub fn validate(self: &Self) -> Vec<LevelError> {
+        let mut errors = Vec::new();
+        if self.0.is_empty() {
+            errors.push(LevelError::Empty);
+        }
+        for (index, b) in self.0.iter().enumerate() {
+            if !(b.max - b.min).cmpgt(IVec3::ZERO).all() {
+                // the remaining checks don't mean much for a block that isn't really there
+                errors.push(LevelError::NoExtent { index, min: b.min, max: b.max });
+                continue;
+            }
+            if b.step < 1 {
+                errors.push(LevelError::BadStep { index, step: b.step });
+            }
+            if b.get_isize() != IVec3::ONE && b.get_elongation().is_none() {
+                errors.push(LevelError::UnsupportedShape { index, size: b.get_isize() });
+            }
+            if b.min.cmplt(IVec3::splat(-Self::MAX_COORD)).any() || b.max.cmpgt(IVec3::splat(Self::MAX_COORD)).any() {
+                errors.push(LevelError::OutOfBounds { index, min: b.min, max: b.max });
+            }
+        }
+        for (first, a) in self.0.iter().enumerate() {
+            for (second, b) in self.0.iter().enumerate().skip(first + 1) {
+                if a.overlaps(b) {
+                    errors.push(LevelError::Overlap { first, second });
+                }
+            }
+        }
+        errors
+    }
 }
 
 #[derive(Resource)]
 pub struct LevelCenter(Vec3);
 
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct LevelSeed(u64);
+
+#[derive(Resource)]
+pub struct LevelBounds(IVec3, IVec3);
+
+#[derive(Resource, Default)]
+pub struct MoveCount(u32);
+
+#[derive(Resource, Default)]
+pub struct LevelTimer {
+    elapsed: f32,
+    running: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct BestTimes(HashMap<u8, f32>);
+
+#[derive(Resource, Default)]
+pub struct DailyBests(HashMap<u32, f32>);
+
+// present while playing the daily challenge for the given YYYYMMDD date
+#[derive(Resource)]
+pub struct DailyChallenge {
+    date: u32,
+}
+
+#[derive(Resource)]
+pub struct LevelCompletion {
+    level: u8,
+    moves: u32,
+    time: f32,
+    new_record: bool,
+    score: u32,
+    // fewest moves that clear the level, if the solver could work it out
+    optimal: Option<u32>,
+}
+
+// points accumulated over every cleared level
+#[derive(Resource, Default)]
+pub struct Score(u32);
+
+// whether the player ran into a dead end on this attempt, which costs the bonus
+#[derive(Resource, Default)]
+pub struct GotStuck(bool);
+
+// present while playing endless mode, where every cleared level is followed by a fresh one
+#[derive(Resource, Default)]
+pub struct EndlessMode {
+    cleared: u32,
+    ramp: generation::EndlessRamp,
+}
+
+// present while playing a freshly generated level picked from the menu with "Regenerate". It
+// holds the level whose size is generated, apart from CurrentLevel which says where the
+// player's progress is, so clearing it neither advances nor sets records
+#[derive(Resource)]
+pub struct PracticeLevel(u8);
+
+#[derive(Component)]
+pub struct MoveCountText;
+
+#[derive(Component)]
+pub struct TimerText;
+
+#[derive(Component)]
+pub struct BlocksLeftText;
+
+// every entry holds all the moves caused by a single click
+#[derive(Resource, Default)]
+pub struct MoveHistory(Vec<Vec<(Entity, block::Block, block::Block)>>);
+
 #[derive(Component, Reflect)]
 pub struct MoveDest {
+    start: Vec3,
     dest: Vec3,
+    elapsed: f32,
+    duration: f32,
     should_despawn: bool
 }
 
-#[derive(Component)]
-pub struct BlockSceneMarker;
+impl MoveDest {
+    // average speed in world units per second
+    const SPEED: f32 = 16.0;
 
-pub fn rotate_axis_to_axis(ax_from: &block::Axis, ax_to: &block::Axis) -> Quat {
-    match ax_from.remaining(ax_to) {
-        None => Quat::IDENTITY,
-        Some(axis_to_rotate_around) => {
-            let angle = (std::f32::consts::PI / 2.0) * (ax_from.cross(ax_to) as f32);
-            Quat::from_axis_angle(
-                axis_to_rotate_around.unit_vector(),
-                angle
-            )
-        }
+    pub fn new(start: Vec3, dest: Vec3, should_despawn: bool) -> Self {
+        let duration = (start.distance(dest) / Self::SPEED).max(f32::EPSILON);
+        Self { start, dest, elapsed: 0.0, duration, should_despawn }
     }
 }
 
-pub fn flip_if_necessary(dir: &block::Direction, ax: &block::Axis) -> Quat {
-    if dir.positive {
-        Quat::IDENTITY
-    }
-    else {
-        Quat::from_axis_angle(
-            ax.unit_vector(), std::f32::consts::PI
-        )
+// multiplies the rate at which moving blocks advance, taken from the settings
+#[derive(Resource)]
+pub struct GameSpeed(f32);
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self(1.0)
     }
 }
 
+fn apply_game_speed(settings: Res<settings::Settings>, mut speed: ResMut<GameSpeed>) {
+    speed.0 = settings.game_speed.clamp(settings::Settings::MIN_GAME_SPEED, settings::Settings::MAX_GAME_SPEED);
+}
+
+#[derive(Component)]
+pub struct BlockSceneMarker;
+
+const BLOCK_SCALE: f32 = 0.5;
+
+#[derive(Component)]
+pub struct SpawnAnim {
+    start_time: f32,
+    delay: f32,
+}
+
+impl SpawnAnim {
+    const DURATION: f32 = 0.3;
+    const STAGGER: f32 = 0.03;
+}
+
+#[derive(Component)]
+pub struct HintPulse {
+    elapsed: f32,
+}
+
+#[derive(Component)]
+pub struct HintText;
+
+#[derive(Component)]
+pub struct StuckBanner;
+
+#[derive(Resource, Default)]
+pub struct HoveredBlock(Option<Entity>);
+
+#[derive(Event)]
+pub struct BlockActivated(Entity);
+
+// sent once the last block of a regular level is gone
+#[derive(Event, Clone, Copy, Debug)]
+pub struct LevelCompleted {
+    pub level: u8,
+    pub moves: u32,
+    pub time: f32,
+}
+
+// where the last press on a block started, to tell taps apart from camera drags
+#[derive(Resource, Default)]
+pub struct PressPosition(Option<Vec2>);
+
+// cached order of blocks the auto-solver will remove next. Working out a new one runs in the
+// background, like OptimalMoves
+#[derive(Resource, Default)]
+pub struct Solution {
+    steps: Vec<block::Block>,
+    task: Option<Task<Option<Vec<block::Block>>>>,
+}
+
+// settled blocks that would fly away if clicked, recomputed whenever the board changes
+#[derive(Resource, Default)]
+pub struct FreeBlocks(HashSet<Entity>);
+
+#[derive(Resource)]
+pub struct InitialLevel(Vec<block::Block>);
+
+// clicks in the shortest solution of the level as it was dealt. The solver can take a while on
+// big boards, so it runs in the background from the moment the level is drawn; None until then,
+// or if the solver gave up
+#[derive(Resource, Default)]
+pub struct OptimalMoves {
+    task: Option<Task<Option<u32>>>,
+    moves: Option<u32>,
+}
+
+#[derive(Component)]
+pub struct GhostMarker;
+
 pub fn block_model_rotation(block: &block::Block, models: &BlockModels) -> (Handle<Scene>, Quat) {
-    let el: Option<block::Axis> = block.get_elongation();
+    let el: Option<(block::Axis, i32)> = block.get_elongation();
     let dir: block::Direction = block.direction;
-    let dir_rotation = flip_if_necessary(&dir, &block::Axis::X);
-    let axis_rotation = rotate_axis_to_axis(&block::Axis::Y, &dir.axis);
+    let dir_rotation = block::flip_if_necessary(&dir, &block::Axis::X);
+    let axis_rotation = block::rotate_axis_to_axis(&block::Axis::Y, &dir.axis);
     match el {
         None => {
             let model = models.small_model.clone();
             let rotation = axis_rotation * dir_rotation;
             (model, rotation)
         }
-        Some(d) =>
+        Some((d, length)) =>
             if d == dir.axis {
                 let rotation = axis_rotation * dir_rotation;
-                (models.long_model.clone(), rotation)
+                let model = if length == 3 { &models.very_long_model } else { &models.long_model };
+                (model.clone(), rotation)
             }
             else {
                 let initial_model_elongation = Vec3::Z;
                 let pre_rotation = axis_rotation * dir_rotation;
                 let model_elongation = pre_rotation.mul_vec3(initial_model_elongation);
+                // pre_rotation only permutes axes, so the elongation is either already along `d`
+                // or along the third axis and needs a quarter turn around the direction axis.
+                // Comparing the dominant component keeps this exact despite float noise
                 let final_rotation =
-                    if model_elongation.abs().abs_diff_eq(d.unit_vector(), 1e-6) { 
+                    if d.vec3_component(model_elongation).abs() > 0.5 {
                         Quat::IDENTITY
                     }
                     else {
                         Quat::from_axis_angle(dir.axis.unit_vector(), std::f32::consts::PI / 2.0)
                     };
                 let rotation = final_rotation * pre_rotation;
-                (models.wide_model.clone(), rotation)
+                let model = if length == 3 { &models.very_wide_model } else { &models.wide_model };
+                (model.clone(), rotation)
+            }
+    }
+}
+
+fn spawn_block(
+    commands: &mut Commands,
+    block: &block::Block,
+    models: &BlockModels,
+    level_center: Vec3,
+) -> Entity {
+    let block_center = block.get_center();
+    let (model, rotation) = block_model_rotation(block, models);
+    commands.spawn((
+        SceneRoot(model),
+        *block,
+        Transform::from_translation(block_center - level_center)
+            .with_scale(Vec3::splat(BLOCK_SCALE))
+            .with_rotation(rotation),
+        BlockSceneMarker,
+    ))
+    .observe(remember_press_position)
+    .observe(send_block_on_click)
+    .observe(drag::grab_block)
+    .observe(drag::preview_block_drag)
+    .observe(drag::release_block_drag)
+    .observe(tint_block_scene)
+    .observe(decal::add_exit_decal)
+    .observe(highlight_on_hover)
+    .observe(unhighlight_on_out)
+    .id()
+}
+
+fn set_block_emissive(
+    root: Entity,
+    emissive: LinearRgba,
+    children: &Query<&Children>,
+    mesh_materials: &Query<&MeshMaterial3d<StandardMaterial>>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    for descendant in children.iter_descendants(root) {
+        let Ok(mesh_material) = mesh_materials.get(descendant) else { continue };
+        if let Some(material) = materials.get_mut(&mesh_material.0) {
+            material.emissive = emissive;
+        }
+    }
+}
+
+// materials are unique per mesh (see tint_block_scene), so this only affects the one block.
+// Opaque blocks stay opaque so they don't pay for transparency sorting
+fn set_block_alpha(
+    root: Entity,
+    alpha: f32,
+    children: &Query<&Children>,
+    mesh_materials: &Query<&MeshMaterial3d<StandardMaterial>>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    for descendant in children.iter_descendants(root) {
+        let Ok(mesh_material) = mesh_materials.get(descendant) else { continue };
+        let Some(material) = materials.get(&mesh_material.0) else { continue };
+        if material.base_color.alpha() == alpha {
+            continue;
+        }
+        let material = materials.get_mut(&mesh_material.0).expect("material was just found");
+        material.base_color.set_alpha(alpha);
+        material.alpha_mode = if alpha < 1.0 { AlphaMode::Blend } else { AlphaMode::Opaque };
+    }
+}
+
+fn highlight_on_hover(
+    trigger: Trigger<Pointer<Over>>,
+    mut hovered: ResMut<HoveredBlock>,
+    children: Query<&Children>,
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let root = trigger.target();
+    hovered.0 = Some(root);
+    set_block_emissive(root, LinearRgba::gray(0.3), &children, &mesh_materials, &mut materials);
+}
+
+fn unhighlight_on_out(
+    trigger: Trigger<Pointer<Out>>,
+    mut hovered: ResMut<HoveredBlock>,
+    children: Query<&Children>,
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let root = trigger.target();
+    if hovered.0 == Some(root) {
+        hovered.0 = None;
+    }
+    set_block_emissive(root, LinearRgba::BLACK, &children, &mesh_materials, &mut materials);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_ghost(
+    mut commands: Commands,
+    hovered: Res<HoveredBlock>,
+    ghosts: Query<Entity, With<GhostMarker>>,
+    blocks: Query<(Entity, &block::Block), Without<MoveDest>>,
+    moving: Query<&block::Block, With<MoveDest>>,
+    changed_blocks: Query<(), Changed<block::Block>>,
+    mut finished_moves: RemovedComponents<MoveDest>,
+    level_center: Res<LevelCenter>,
+    level_bounds: Res<LevelBounds>,
+    index: Res<block::BlockIndex>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let board_changed = !changed_blocks.is_empty() || finished_moves.read().count() > 0;
+    if !hovered.is_changed() && !board_changed {
+        return;
+    }
+    ghosts.iter().for_each(|e| commands.entity(e).despawn());
+    let Some(entity_id) = hovered.0 else { return };
+    let Ok((_, block)) = blocks.get(entity_id) else { return };
+    let all_entities: Vec<(Entity, block::Block)> = blocks.iter().map(|(e, b)| (e, *b)).collect();
+    let moving: Vec<block::Block> = moving.iter().copied().collect();
+    let moves = plan_moves(entity_id, block, all_entities.as_slice(), &moving, &index, &level_bounds);
+    let Some((_, dest, _)) = moves.iter().find(|(e, _, _)| *e == entity_id) else { return };
+    let color = direction_color(&block.direction).with_alpha(0.35);
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::from_size(dest.get_size()))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        })),
+        Transform::from_translation(dest.get_center() - level_center.0),
+        Pickable::IGNORE,
+        GhostMarker,
+        BlockSceneMarker,
+    ));
+}
+
+pub fn direction_color(dir: &block::Direction) -> Color {
+    let brightness = if dir.positive { 1.0 } else { 0.55 };
+    let (r, g, b) = match dir.axis {
+        block::Axis::X => (0.9, 0.15, 0.15),
+        block::Axis::Y => (0.15, 0.9, 0.15),
+        block::Axis::Z => (0.15, 0.25, 0.9),
+    };
+    Color::srgb(r * brightness, g * brightness, b * brightness)
+}
+
+fn tint_block_scene(
+    trigger: Trigger<SceneInstanceReady>,
+    mut commands: Commands,
+    blocks: Query<&block::Block, With<BlockSceneMarker>>,
+    children: Query<&Children>,
+    // the decal keeps its own color
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>, Without<decal::ExitDecal>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    locked: Option<Res<overlay::LockedBlocks>>,
+) {
+    let root = trigger.target();
+    let Ok(block) = blocks.get(root) else { return };
+    let color = if locked.is_some_and(|locked| locked.0.contains(block)) {
+        overlay::LOCKED_COLOR
+    } else if block.step != block::Block::DEFAULT_STEP {
+        // blocks leaping more than a cell at a time are paler than the rest
+        direction_color(&block.direction).mix(&Color::WHITE, 0.4)
+    } else {
+        direction_color(&block.direction)
+    };
+    for descendant in children.iter_descendants(root) {
+        let Ok(mesh_material) = mesh_materials.get(descendant) else { continue };
+        let mut material = materials.get(&mesh_material.0).cloned().unwrap_or_default();
+        material.base_color = color;
+        commands.entity(descendant).insert(MeshMaterial3d(materials.add(material)));
+    }
+}
+
+fn draw_blocks(
+    mut commands: Commands,
+    level: &Level,
+    models: &BlockModels,
+    now: f32,
+) {
+    let level_center = level.center();
+    for (i, b) in level.0.iter().enumerate() {
+        let entity = spawn_block(&mut commands, b, models, level_center);
+        commands.entity(entity).insert(SpawnAnim { start_time: now, delay: i as f32 * SpawnAnim::STAGGER });
+        commands.entity(entity).entry::<Transform>().and_modify(|mut tr| tr.scale = Vec3::ZERO);
+    }
+    let (lower, upper) = level.bounds();
+    commands.insert_resource(LevelCenter(level_center));
+    commands.insert_resource(LevelBounds(lower.as_ivec3(), upper.as_ivec3()));
+    commands.insert_resource(MoveHistory::default());
+    commands.insert_resource(InitialLevel(level.0.clone()));
+    commands.insert_resource(replay::Replay::new(level.0.clone()));
+}
+
+// loaded once at startup, the resource keeps the handles alive across levels
+fn load_block_models(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let small_model = asset_server.load("small_model.glb#Scene0");
+    let wide_model = asset_server.load("wide_model.glb#Scene0");
+    let long_model = asset_server.load("long_model.glb#Scene0");
+    let very_wide_model = asset_server.load("very_wide_model.glb#Scene0");
+    let very_long_model = asset_server.load("very_long_model.glb#Scene0");
+    commands.insert_resource(BlockModels { small_model, wide_model, long_model, very_wide_model, very_long_model });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    models: Res<BlockModels>,
+    current_level: Res<CurrentLevel>,
+    difficulty: Res<CurrentDifficulty>,
+    edited_level: Option<Res<editor::EditedLevel>>,
+    endless: Option<Res<EndlessMode>>,
+    daily: Option<Res<DailyChallenge>>,
+    practice: Option<Res<PracticeLevel>>,
+    play_seed: Option<Res<seed_entry::PlaySeed>>,
+    mut lstate: ResMut<NextState<LevelLoadingState>>,
+    show_locked: Res<overlay::ShowLockedBlocks>,
+    settings: Res<settings::Settings>,
+    time: Res<Time>,
+) {
+    commands.remove_resource::<overlay::LockedBlocks>();
+    commands.remove_resource::<cutaway::CutawayPlane>();
+    commands.remove_resource::<replay::ReplayPlayback>();
+    commands.remove_resource::<tutorial::TutorialPrompts>();
+    commands.spawn((
+        Camera3d::default(),
+        PanOrbitCamera::default(),
+        bevy_egui::PrimaryEguiContext,
+        Transform::from_xyz(0.0, 10.0, 20.0).looking_at(Vec3::ZERO, Vec3::Y),
+        BlockSceneMarker,
+    ));
+    lighting::spawn_light_rig(&mut commands, &settings);
+    // practice always gets a generated level, even where the level number has a hand-made one
+    let level = practice.as_deref().map_or(current_level.0, |p| p.0);
+    let authored = if practice.is_some() || play_seed.is_some() { None } else { level_file(level) };
+    let tutorial = edited_level.is_none() && endless.is_none() && daily.is_none()
+        && authored.is_some() && tutorial::level_path(level).is_some();
+    match (edited_level, endless.as_deref(), daily.as_deref(), authored) {
+        (Some(edited_level), _, _, _) => {
+            draw_blocks(commands.reborrow(), &Level(edited_level.0.clone()), &models, time.elapsed_secs());
+            lstate.set(LevelLoadingState::Level);
+        },
+        (None, Some(endless), _, _) => {
+            draw_endless_level(commands.reborrow(), endless, difficulty.0, &models, time.elapsed_secs());
+            lstate.set(LevelLoadingState::Level);
+        },
+        (None, None, Some(daily), _) => {
+            draw_daily_level(commands.reborrow(), daily, &models, time.elapsed_secs());
+            lstate.set(LevelLoadingState::Level);
+        },
+        (None, None, None, Some(path)) => {
+            commands.insert_resource(LevelHandle(asset_server.load(path)));
+        },
+        (None, None, None, None) => {
+            match play_seed.as_deref() {
+                Some(play_seed) => draw_seeded_level(commands.reborrow(), play_seed, difficulty.0, &models, time.elapsed_secs()),
+                None => draw_generated_level(commands.reborrow(), level, difficulty.0, show_locked.0, &models, time.elapsed_secs()),
+            }
+            lstate.set(LevelLoadingState::Level);
+        },
+    }
+    commands.insert_resource(audio::load_sounds(&asset_server));
+    commands.insert_resource(MoveCount::default());
+    commands.insert_resource(LevelTimer { elapsed: 0.0, running: true });
+    commands.insert_resource(GotStuck::default());
+    commands.insert_resource(achievements::UsedUndo::default());
+    // endless mode is meant to be relaxed, so it shows neither moves nor time
+    if endless.is_none() {
+        commands.spawn((hud_text("Moves: 0", 10.0), MoveCountText, BlockSceneMarker));
+        commands.spawn((hud_text("Time: 0.0", 40.0), TimerText, BlockSceneMarker));
+    }
+    commands.spawn((hud_text("", 70.0), BlocksLeftText, BlockSceneMarker));
+    commands.spawn((hud_text("", 100.0), HintText, BlockSceneMarker));
+    // shown so it can be passed on to someone else
+    if let Some(play_seed) = &play_seed {
+        commands.spawn((hud_text(&format!("Seed: {}", play_seed.seed), 130.0), BlockSceneMarker));
+    }
+    if tutorial {
+        tutorial::start_tutorial(&mut commands, level);
+    }
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+        children![button("Reset level", ButtonAction::ResetLevel)],
+        BlockSceneMarker,
+    ));
+}
+
+fn show_hint(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    blocks: Query<(Entity, &block::Block, Option<&MoveDest>)>,
+    mut texts: Query<&mut Text, With<HintText>>,
+    level_bounds: Res<LevelBounds>,
+) {
+    if !bindings.just_pressed(&keys, keybindings::Action::Hint) {
+        return;
+    }
+    let remaining: Vec<(Entity, block::Block)> = blocks.iter()
+        .filter(|(_, _, m)| !m.is_some_and(|m| m.should_despawn))
+        .map(|(e, b, _)| (e, *b))
+        .collect();
+    let board: Vec<block::Block> = remaining.iter().map(|t| t.1).collect();
+    let LevelBounds(lower, upper) = *level_bounds;
+    match generation::best_hint(board.as_slice(), (lower, upper)) {
+        Some(i) => {
+            commands.entity(remaining[i].0).insert(HintPulse { elapsed: 0.0 });
+        },
+        None if !board.is_empty() => {
+            for mut text in texts.iter_mut() {
+                text.0 = String::from("Stuck: no block can be removed");
+            }
+        },
+        None => (),
+    }
+}
+
+// blocks still moving count as neither free nor in the way, like for clicks.
+// Only the settled board is looked at, so a block is free once it has come to rest
+fn update_free_blocks(
+    changed: Query<(), Changed<block::Block>>,
+    added_dests: Query<(), Added<MoveDest>>,
+    mut removed_blocks: RemovedComponents<block::Block>,
+    mut removed_dests: RemovedComponents<MoveDest>,
+    settled: Query<(Entity, &block::Block), Without<MoveDest>>,
+    mut free: ResMut<FreeBlocks>,
+) {
+    let removed = removed_blocks.read().count() + removed_dests.read().count();
+    if changed.is_empty() && added_dests.is_empty() && removed == 0 {
+        return;
+    }
+    let (entities, board): (Vec<Entity>, Vec<block::Block>) = settled.iter().map(|(e, b)| (e, *b)).unzip();
+    free.0 = generation::free_blocks(&board).into_iter().map(|i| entities[i]).collect();
+}
+
+// checked once the board has settled after a change, with the same rule the solver uses
+fn detect_stuck(
+    mut commands: Commands,
+    blocks: Query<(), With<block::Block>>,
+    free: Res<FreeBlocks>,
+    in_progress: Res<AnimationInProgress>,
+    banners: Query<Entity, With<StuckBanner>>,
+    mut dirty: Local<bool>,
+) {
+    if free.is_changed() {
+        *dirty = true;
+        banners.iter().for_each(|e| commands.entity(e).despawn());
+    }
+    if !*dirty || in_progress.0 {
+        return;
+    }
+    *dirty = false;
+    let stuck = !blocks.is_empty() && free.0.is_empty();
+    if stuck {
+        commands.insert_resource(GotStuck(true));
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(20.0),
+                width: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(10.0),
+                ..default()
+            },
+            children![
+                text(String::from("Stuck!")),
+                button("Undo", ButtonAction::UndoMove),
+                button("Restart", ButtonAction::ResetLevel),
+            ],
+            StuckBanner,
+            BlockSceneMarker,
+        ));
+    }
+}
+
+fn clear_hint_text(mut texts: Query<&mut Text, With<HintText>>) {
+    for mut text in texts.iter_mut() {
+        text.0.clear();
+    }
+}
+
+fn pulse_hinted_blocks(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut HintPulse)>,
+    time: Res<Time>,
+) {
+    const DURATION: f32 = 1.0;
+    for (entity_id, mut tr, mut pulse) in query.iter_mut() {
+        pulse.elapsed += time.delta_secs();
+        if pulse.elapsed >= DURATION {
+            tr.scale = Vec3::splat(BLOCK_SCALE);
+            commands.entity(entity_id).remove::<HintPulse>();
+        }
+        else {
+            let wave = (pulse.elapsed * std::f32::consts::TAU * 2.0).sin().abs();
+            tr.scale = Vec3::splat(BLOCK_SCALE * (1.0 + 0.2 * wave));
+        }
+    }
+}
+
+type LevelEntityFilter = Or<(With<block::Block>, With<GhostMarker>)>;
+
+#[allow(clippy::too_many_arguments)]
+fn reset_level(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    level_entities: Query<Entity, LevelEntityFilter>,
+    initial_level: Res<InitialLevel>,
+    models: Res<BlockModels>,
+    mut move_count: ResMut<MoveCount>,
+    mut timer: ResMut<LevelTimer>,
+    time: Res<Time>,
+) {
+    let button_pressed = interaction_query.iter()
+        .any(|(i, a)| *i == Interaction::Pressed && *a == ButtonAction::ResetLevel);
+    if !bindings.just_pressed(&keys, keybindings::Action::Reset) && !button_pressed {
+        return;
+    }
+    level_entities.iter().for_each(|e| commands.entity(e).despawn());
+    commands.insert_resource(GotStuck::default());
+    commands.insert_resource(achievements::UsedUndo::default());
+    draw_blocks(commands, &Level(initial_level.0.clone()), &models, time.elapsed_secs());
+    *move_count = MoveCount::default();
+    *timer = LevelTimer { elapsed: 0.0, running: true };
+}
+
+// Q keeps the shapes of the level but deals out new directions, and starts it over
+#[allow(clippy::too_many_arguments)]
+fn reshuffle_level(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    moving: Query<(), Animating>,
+    level_entities: Query<Entity, LevelEntityFilter>,
+    initial_level: Res<InitialLevel>,
+    models: Res<BlockModels>,
+    mut move_count: ResMut<MoveCount>,
+    mut timer: ResMut<LevelTimer>,
+    time: Res<Time>,
+) {
+    if !bindings.just_pressed(&keys, keybindings::Action::Reshuffle) || !moving.is_empty() {
+        return;
+    }
+    let mut blocks = initial_level.0.clone();
+    if !generation::reassign_directions(&mut blocks, &mut rand::rng()) {
+        info!("found no solvable set of directions for this level");
+        return;
+    }
+    level_entities.iter().for_each(|e| commands.entity(e).despawn());
+    commands.insert_resource(GotStuck::default());
+    commands.insert_resource(achievements::UsedUndo::default());
+    draw_blocks(commands, &Level(blocks), &models, time.elapsed_secs());
+    *move_count = MoveCount::default();
+    *timer = LevelTimer { elapsed: 0.0, running: true };
+}
+
+// applies a rigid transform of the grid to everything that describes the current level, so
+// undo, reset and the camera framing keep working on the transformed board
+fn transform_level(
+    commands: &mut Commands,
+    transform: impl Fn(&block::Block) -> block::Block,
+    blocks: &mut Query<(&mut block::Block, &mut Transform)>,
+    history: &mut MoveHistory,
+    initial_level: &mut InitialLevel,
+    replay: &mut replay::Replay,
+    models: &BlockModels,
+) {
+    initial_level.0 = initial_level.0.iter().map(&transform).collect();
+    replay.transform(&transform);
+    for (_, before, after) in history.0.iter_mut().flatten() {
+        (*before, *after) = (transform(before), transform(after));
+    }
+    // isometries of the grid map the level's bounding box onto the new one
+    let initial = Level(initial_level.0.clone());
+    let (lower, upper) = initial.bounds();
+    let level_center = initial.center();
+    for (mut block, mut tr) in blocks.iter_mut() {
+        *block = transform(&block);
+        tr.translation = block.get_center() - level_center;
+        tr.rotation = block_model_rotation(&block, models).1;
+    }
+    commands.insert_resource(LevelCenter(level_center));
+    commands.insert_resource(LevelBounds(lower.as_ivec3(), upper.as_ivec3()));
+}
+
+// the board transforms work on the vertical axis, or on X with Shift and Z with Ctrl held
+fn chosen_axis(keys: &ButtonInput<KeyCode>) -> block::Axis {
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        block::Axis::X
+    } else if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        block::Axis::Z
+    } else {
+        block::Axis::Y
+    }
+}
+
+// T turns the board a quarter turn around the chosen axis
+#[allow(clippy::too_many_arguments)]
+fn rotate_level(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    moving: Query<(), Animating>,
+    mut blocks: Query<(&mut block::Block, &mut Transform)>,
+    mut history: ResMut<MoveHistory>,
+    mut initial_level: ResMut<InitialLevel>,
+    mut replay: ResMut<replay::Replay>,
+    models: Res<BlockModels>,
+) {
+    if !bindings.just_pressed(&keys, keybindings::Action::Rotate) || !moving.is_empty() {
+        return;
+    }
+    let axis = chosen_axis(&keys);
+    transform_level(&mut commands, |b| b.rotate_around(axis), &mut blocks, &mut history, &mut initial_level, &mut replay, &models);
+}
+
+// M plays the mirror image of the board, reflected along the chosen axis
+#[allow(clippy::too_many_arguments)]
+fn mirror_level(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    moving: Query<(), Animating>,
+    mut blocks: Query<(&mut block::Block, &mut Transform)>,
+    mut history: ResMut<MoveHistory>,
+    mut initial_level: ResMut<InitialLevel>,
+    mut replay: ResMut<replay::Replay>,
+    models: Res<BlockModels>,
+) {
+    if !bindings.just_pressed(&keys, keybindings::Action::Mirror) || !moving.is_empty() {
+        return;
+    }
+    let axis = chosen_axis(&keys);
+    transform_level(&mut commands, |b| b.mirror(axis), &mut blocks, &mut history, &mut initial_level, &mut replay, &models);
+}
+
+fn hud_text(content: &str, top: f32) -> impl Bundle {
+    (
+        Text::new(content),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+        TextShadow::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(top),
+            left: Val::Px(10.0),
+            ..default()
+        },
+    )
+}
+
+fn tick_level_timer(
+    mut timer: ResMut<LevelTimer>,
+    time: Res<Time>,
+    mut texts: Query<&mut Text, With<TimerText>>,
+    spawning: Query<(), With<SpawnAnim>>,
+) {
+    if timer.running && spawning.is_empty() {
+        timer.elapsed += time.delta_secs();
+    }
+    for mut text in texts.iter_mut() {
+        text.0 = format!("Time: {:.1}", timer.elapsed);
+    }
+}
+
+// blocks already flying away count as cleared
+fn update_blocks_left_text(
+    blocks: Query<Option<&MoveDest>, With<block::Block>>,
+    mut texts: Query<&mut Text, With<BlocksLeftText>>,
+) {
+    let left = blocks.iter().filter(|m| !m.is_some_and(|m| m.should_despawn)).count();
+    let content = format!("Blocks left: {}", left);
+    for mut text in texts.iter_mut() {
+        // only touch the text when the count changes, so it isn't re-laid out every frame
+        if text.0 != content {
+            text.0.clone_from(&content);
+        }
+    }
+}
+
+fn update_move_count_text(
+    move_count: Res<MoveCount>,
+    mut texts: Query<&mut Text, With<MoveCountText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.0 = format!("Moves: {}", move_count.0);
+    }
+}
+
+fn write_level_file(level: &Level) -> std::io::Result<std::path::PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let dir = std::path::Path::new("levels");
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("level_{}.json", timestamp));
+    let json = serde_json::to_string_pretty(level).map_err(std::io::Error::other)?;
+    std::fs::write(&path, json)?;
+    Ok(path)
+}
+
+fn export_level(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    blocks: Query<(&block::Block, Option<&MoveDest>)>,
+) {
+    if !bindings.just_pressed(&keys, keybindings::Action::Export) {
+        return;
+    }
+    let level = Level(blocks.iter()
+        .filter(|(_, m)| !m.is_some_and(|m| m.should_despawn))
+        .map(|(b, _)| *b)
+        .collect());
+    match write_level_file(&level) {
+        Ok(path) => info!("exported level to {}", path.display()),
+        Err(err) => error!("failed to export level: {}", err),
+    }
+}
+
+fn level_file(level: u8) -> Option<String> {
+    if let Some(path) = tutorial::level_path(level) {
+        return Some(path);
+    }
+    let path = format!("level{}.json", level);
+    std::path::Path::new("assets").join(&path).exists().then_some(path)
+}
+
+fn draw_generated_level(
+    mut commands: Commands,
+    level: u8,
+    difficulty: generation::Difficulty,
+    keep_locked: bool,
+    models: &BlockModels,
+    now: f32,
+) {
+    let width = difficulty.side_len(level);
+    let params = difficulty.level_params(level);
+    let seed = generation::random_seed();
+    commands.insert_resource(LevelSeed(seed));
+    let blocks = if keep_locked {
+        let (blocks, locked) = generation::generate_level_keeping_locked(width, seed, &params)
+            .unwrap_or_else(|err| {
+                error!("failed to generate a level from seed {}: {}", seed, err);
+                (Vec::new(), Vec::new())
+            });
+        for generation::LockedBlock { block, axis, line } in &locked {
+            info!("locked {:?} at {}..{} on the {:?} line through {}", block.direction, block.min, block.max, axis, line);
+        }
+        commands.insert_resource(overlay::LockedBlocks(locked.into_iter().map(|l| l.block).collect()));
+        blocks
+    } else {
+        generate_or_log(width, seed, &params)
+    };
+    draw_blocks(commands, &Level(blocks), models, now);
+}
+
+// a level that can't be generated is replaced by a single block rather than taking the whole
+// game down. An empty level would count as cleared straight away, a lone block is always free
+fn generate_or_log(side_len: u8, seed: u64, params: &generation::GenParams) -> Vec<block::Block> {
+    generation::generate_level_seeded(side_len, seed, params).unwrap_or_else(|err| {
+        error!("failed to generate a level from seed {}: {}", seed, err);
+        vec![block::Block::new(block::Direction::YP, IVec3::ZERO, IVec3::ONE)]
+    })
+}
+
+fn draw_endless_level(
+    mut commands: Commands,
+    endless: &EndlessMode,
+    difficulty: generation::Difficulty,
+    models: &BlockModels,
+    now: f32,
+) {
+    let seed = generation::random_seed();
+    commands.insert_resource(LevelSeed(seed));
+    let blocks = generate_or_log(endless.ramp.side_len(endless.cleared), seed, &difficulty.params());
+    draw_blocks(commands, &Level(blocks), models, now);
+}
+
+fn draw_seeded_level(
+    mut commands: Commands,
+    play_seed: &seed_entry::PlaySeed,
+    difficulty: generation::Difficulty,
+    models: &BlockModels,
+    now: f32,
+) {
+    commands.insert_resource(LevelSeed(play_seed.seed));
+    let blocks = generate_or_log(play_seed.side_len, play_seed.seed, &difficulty.params());
+    draw_blocks(commands, &Level(blocks), models, now);
+}
+
+fn draw_daily_level(mut commands: Commands, daily: &DailyChallenge, models: &BlockModels, now: f32) {
+    let seed = daily::date_seed(daily.date);
+    commands.insert_resource(LevelSeed(seed));
+    let params = generation::Difficulty::Medium.params();
+    draw_blocks(commands, &Level(generate_or_log(daily::SIDE_LEN, seed, &params)), models, now);
+}
+
+// replaces finish_level_if_done in endless mode: no progress is recorded, the next level just appears
+#[allow(clippy::too_many_arguments)]
+fn continue_endless(
+    mut commands: Commands,
+    blocks_query: Query<&block::Block>,
+    level_entities: Query<Entity, LevelEntityFilter>,
+    mut endless: ResMut<EndlessMode>,
+    difficulty: Res<CurrentDifficulty>,
+    models: Res<BlockModels>,
+    mut move_count: ResMut<MoveCount>,
+    time: Res<Time>,
+) {
+    if !blocks_query.is_empty() {
+        return;
+    }
+    level_entities.iter().for_each(|e| commands.entity(e).despawn());
+    endless.cleared += 1;
+    commands.insert_resource(GotStuck::default());
+    commands.insert_resource(achievements::UsedUndo::default());
+    draw_endless_level(commands, &endless, difficulty.0, &models, time.elapsed_secs());
+    *move_count = MoveCount::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn wait_for_level_asset(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<Level>>,
+    handle: Res<LevelHandle>,
+    models: Res<BlockModels>,
+    current_level: Res<CurrentLevel>,
+    difficulty: Res<CurrentDifficulty>,
+    mut lstate: ResMut<NextState<LevelLoadingState>>,
+    show_locked: Res<overlay::ShowLockedBlocks>,
+    time: Res<Time>,
+) {
+    match asset_server.load_state(handle.0.id()) {
+        LoadState::Loaded => {
+            let level = levels.get(handle.0.id()).expect("loaded level asset should be available");
+            let errors = level.validate();
+            if errors.is_empty() {
+                let (lower, upper) = level.bounds();
+                if let sim::Outcome::Stuck { remaining } = sim::play_greedy(level.0.clone(), (lower.as_ivec3(), upper.as_ivec3())).outcome {
+                    warn!("level {}: a player only clicking free blocks gets stuck with {} left", current_level.0, remaining);
+                }
+                draw_blocks(commands.reborrow(), level, &models, time.elapsed_secs());
+            } else {
+                for err in &errors {
+                    error!("level {}: {}", current_level.0, err);
+                }
+                draw_generated_level(commands.reborrow(), current_level.0, difficulty.0, show_locked.0, &models, time.elapsed_secs());
             }
+        },
+        LoadState::Failed(err) => {
+            error!("failed to load level {}: {}", current_level.0, err);
+            draw_generated_level(commands.reborrow(), current_level.0, difficulty.0, show_locked.0, &models, time.elapsed_secs());
+        },
+        _ => return,
+    }
+    commands.remove_resource::<LevelHandle>();
+    lstate.set(LevelLoadingState::Level);
+}
+
+// returns the new position of every block affected by clicking `block`,
+// and whether it should be despawned once it arrives. `moving` are the blocks still animating,
+// at the positions they are heading to
+fn plan_moves(
+    entity_id: Entity,
+    block: &block::Block,
+    all_entities: &[(Entity, block::Block)],
+    moving: &[block::Block],
+    index: &block::BlockIndex,
+    level_bounds: &LevelBounds,
+) -> Vec<(Entity, block::Block, bool)> {
+    let in_front = index.blocks_in_front(block);
+    let axis = block.direction.axis;
+    let LevelBounds(lower, upper) = *level_bounds;
+    let bound = if block.direction.positive { axis.ivec3_component(upper) } else { axis.ivec3_component(lower) };
+    let board: Vec<block::Block> = all_entities.iter().map(|t| t.1).collect();
+    let pushed = if in_front.is_empty() { None } else { block.push_line(&board, bound) };
+    match pushed {
+        Some(moves) => moves.iter()
+            .filter_map(|(old, new)| {
+                all_entities.iter()
+                    .find(|(_, b)| b == old)
+                    .map(|(e, _)| (*e, *new, false))
+            })
+            .collect(),
+        // only an empty path lets the block fly away. A blocker it can't be placed in front of
+        // shouldn't happen, but leaves the block where it is rather than sending it through
+        None => match index.nearest_block_in_front(block) {
+            Some(blocker) => match block.move_block(&blocker) {
+                Some(new_block) => vec![(entity_id, new_block, false)],
+                None => {
+                    warn!("block at {}..{} can't stop in front of {}..{}", block.min, block.max, blocker.min, blocker.max);
+                    Vec::new()
+                },
+            },
+            None => {
+                let present: Vec<block::Block> = all_entities.iter().map(|t| t.1).chain(moving.iter().copied()).collect();
+                vec![(entity_id, get_flyaway_block_position(block, level_bounds, &present), true)]
+            },
+        },
+    }
+}
+
+// collisions only consider settled blocks, like the clicks themselves
+fn rebuild_block_index(
+    changed: Query<(), Changed<block::Block>>,
+    mut removed_blocks: RemovedComponents<block::Block>,
+    mut removed_dests: RemovedComponents<MoveDest>,
+    settled: Query<&block::Block, Without<MoveDest>>,
+    mut index: ResMut<block::BlockIndex>,
+) {
+    let removed = removed_blocks.read().count() + removed_dests.read().count();
+    if changed.is_empty() && removed == 0 {
+        return;
+    }
+    *index = block::BlockIndex::new(settled.iter().copied());
+}
+
+// blocks can only be clicked once they have settled
+type ClickableBlock = (With<block::Block>, Without<MoveDest>, Without<SpawnAnim>);
+type Animating = Or<(With<MoveDest>, With<SpawnAnim>)>;
+
+// whether any block is still sliding, flying away or popping in, so systems that should only
+// look at a settled board can be gated on it
+#[derive(Resource, Default, PartialEq)]
+pub struct AnimationInProgress(bool);
+
+fn update_animation_in_progress(
+    animating: Query<(), Animating>,
+    mut in_progress: ResMut<AnimationInProgress>,
+) {
+    in_progress.set_if_neq(AnimationInProgress(!animating.is_empty()));
+}
+
+fn animation_in_progress(in_progress: Res<AnimationInProgress>) -> bool {
+    in_progress.0
+}
+
+// a press that moves further than this (in pixels) was orbiting the camera, not clicking
+const TAP_SLOP: f32 = 10.0;
+// touch screens have no middle button, so holding a block stands in for it
+const LONG_PRESS_SECS: f32 = 0.5;
+
+fn remember_press_position(
+    press: Trigger<Pointer<Pressed>>,
+    mut press_position: ResMut<PressPosition>,
+) {
+    press_position.0 = Some(press.pointer_location.position);
+}
+
+fn send_block_on_click(
+    click: Trigger<Pointer<Click>>,
+    clickable: Query<(), ClickableBlock>,
+    mut activated: EventWriter<BlockActivated>,
+    mut selected: ResMut<selection::Selected>,
+    interface: Res<State<Interface>>,
+    press_position: Res<PressPosition>,
+    touches: Res<Touches>,
+) {
+    if *interface.get() != Interface::Gameplay {
+        return;
+    }
+    if !clickable.contains(click.target()) {
+        return;
+    }
+    let dragged = press_position.0
+        .is_some_and(|start| start.distance(click.pointer_location.position) > TAP_SLOP);
+    if dragged {
+        return;
+    }
+    use PointerButton as P;
+    // a long press, or a second finger still on the screen, acts as a middle click
+    let touch_info = click.pointer_id.is_touch()
+        && (click.event.duration.as_secs_f32() >= LONG_PRESS_SECS || touches.iter().next().is_some());
+    let button = if touch_info { P::Middle } else { click.event.button };
+    match button {
+        // shows the block in the info panel without moving it
+        P::Middle => {
+            selected.0 = Some(click.target());
+        },
+        P::Primary => {
+            activated.write(BlockActivated(click.target()));
+        },
+        _ => (),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_activated_blocks(
+    mut commands: Commands,
+    mut activated: EventReader<BlockActivated>,
+    mut blocks: Query<(Entity, &mut block::Block, &Transform), Without<MoveDest>>,
+    moving: Query<&block::Block, With<MoveDest>>,
+    level_center: Res<LevelCenter>,
+    level_bounds: Res<LevelBounds>,
+    mut index: ResMut<block::BlockIndex>,
+    mut history: ResMut<MoveHistory>,
+    mut move_count: ResMut<MoveCount>,
+    sounds: Res<audio::SoundAssets>,
+    settings: Res<settings::Settings>,
+    timer: Res<LevelTimer>,
+    mut replay: ResMut<replay::Replay>,
+) {
+    let moving: Vec<block::Block> = moving.iter().copied().collect();
+    for BlockActivated(entity_id) in activated.read() {
+        let all_entities: Vec<(Entity, block::Block)> = blocks.iter().map(|t| (t.0, *t.1)).collect();
+        let Ok((_, block, _)) = blocks.get(*entity_id) else { continue };
+        let block = *block;
+        let moves = plan_moves(*entity_id, &block, all_entities.as_slice(), &moving, &index, &level_bounds);
+        let flew_away = moves.iter().any(|(_, _, should_despawn)| *should_despawn);
+        let mut records = Vec::new();
+        for (entity, new_block, should_despawn) in moves {
+            let Ok((_, mut old_block, tr)) = blocks.get_mut(entity) else { continue };
+            if new_block != *old_block {
+                commands.entity(entity).insert(MoveDest::new(
+                    tr.translation,
+                    new_block.get_center() - level_center.0,
+                    should_despawn,
+                ));
+                records.push((entity, *old_block, new_block));
+                // moving blocks don't block anything until they settle and the index is rebuilt
+                index.remove(&old_block);
+                *old_block = new_block;
+            }
+        }
+        if !records.is_empty() {
+            let moved: Vec<(block::Block, block::Block)> = records.iter().map(|(_, before, after)| (*before, *after)).collect();
+            replay.record(timer.elapsed, block, &moved);
+            let clip = if flew_away { &sounds.whoosh } else { &sounds.slide };
+            audio::play_sfx(&mut commands, clip, &settings);
+            history.0.push(records);
+            move_count.0 += 1;
+        }
+    }
+}
+
+fn auto_solve(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    blocks: Query<(Entity, &block::Block)>,
+    moving: Query<(), Animating>,
+    level_bounds: Res<LevelBounds>,
+    mut solution: ResMut<Solution>,
+    mut activated: EventWriter<BlockActivated>,
+) {
+    if !bindings.pressed(&keys, keybindings::Action::AutoSolve) || !moving.is_empty() {
+        return;
+    }
+    if let Some(task) = &mut solution.task {
+        let Some(solved) = block_on(future::poll_once(task)) else { return };
+        solution.task = None;
+        match solved {
+            Some(steps) => solution.steps = steps,
+            None => {
+                info!("level is unsolvable from the current position");
+                solution.steps.clear();
+                return;
+            },
+        }
+    }
+    let board: Vec<(Entity, block::Block)> = blocks.iter().map(|(e, b)| (e, *b)).collect();
+    // the steps can go stale while they were being worked out, too
+    let next_is_on_board = solution.steps.first()
+        .is_some_and(|next| board.iter().any(|(_, b)| b == next));
+    if !next_is_on_board {
+        let current: Vec<block::Block> = board.iter().map(|t| t.1).collect();
+        let LevelBounds(lower, upper) = *level_bounds;
+        solution.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+            generation::solve(current.as_slice(), (lower, upper))
+        }));
+        return;
+    }
+    let next = solution.steps.remove(0);
+    if let Some((entity_id, _)) = board.iter().find(|(_, b)| *b == next) {
+        activated.write(BlockActivated(*entity_id));
+    }
+}
+
+// cells between the level's bounds and where a flying block ends up, so it fully clears the cube
+const FLYAWAY_MARGIN: i32 = 2;
+
+// `present` is every block still on the board or on its way somewhere, so a block flying out
+// behind another one that is still leaving ends up past it instead of clipping through it
+fn get_flyaway_block_position(block: &block::Block, level_bounds: &LevelBounds, present: &[block::Block]) -> block::Block {
+    let block::Block { direction, min, max, step } = *block;
+    let axis = direction.axis;
+    let LevelBounds(lower, upper) = *level_bounds;
+    let bound = if direction.positive { axis.ivec3_component(upper) } else { axis.ivec3_component(lower) };
+    let edge = block.exit_edge(present.iter().copied(), bound);
+    // at least the margin, rounded up to whole leaps so the block still ends on a multiple of its step
+    let travel = if direction.positive {
+        edge + FLYAWAY_MARGIN - axis.ivec3_component(min)
+    } else {
+        axis.ivec3_component(max) - edge + FLYAWAY_MARGIN
+    };
+    let leap = step.max(1);
+    let travel = (travel + leap - 1) / leap * leap * direction.sign();
+    block::Block {
+        min: axis.set_ivec3_component(&min, axis.ivec3_component(min) + travel),
+        max: axis.set_ivec3_component(&max, axis.ivec3_component(max) + travel),
+        ..*block
     }
 }
 
-fn draw_blocks(
+fn animate_spawning_blocks(
     mut commands: Commands,
-    level: &Level,
-    models: BlockModels,
+    mut query: Query<(Entity, &mut Transform, &SpawnAnim)>,
+    time: Res<Time>,
 ) {
-    let level_center = level.center();
-    for b in level.0.iter() {
-        let block_center = b.get_center();
-        let (model, rotation) = block_model_rotation(b, &models);
-        commands.spawn((
-            SceneRoot(model),
-            *b,
-            Transform::from_translation(block_center - level_center)
-                .with_scale(Vec3::splat(0.5))
-                .with_rotation(rotation),
-            BlockSceneMarker,
-        ))
-        .observe(send_block_on_click);
+    for (entity_id, mut tr, anim) in query.iter_mut() {
+        let t = (time.elapsed_secs() - anim.start_time - anim.delay) / SpawnAnim::DURATION;
+        if t >= 1.0 {
+            tr.scale = Vec3::splat(BLOCK_SCALE);
+            commands.entity(entity_id).remove::<SpawnAnim>();
+        }
+        else {
+            tr.scale = Vec3::splat(BLOCK_SCALE * EaseFunction::BackOut.sample_clamped(t.max(0.0)));
+        }
     }
-    commands.insert_resource(LevelCenter(level_center));
 }
 
-fn setup_level(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    current_level: Res<CurrentLevel>,
-) {
-    let small_model = asset_server.load("small_model.glb#Scene0");
-    let wide_model = asset_server.load("wide_model.glb#Scene0");
-    let long_model = asset_server.load("long_model.glb#Scene0");
-    let models = BlockModels { small_model, wide_model, long_model };
-    commands.spawn((
-        Camera3d::default(),
-        PanOrbitCamera::default(),
-        Transform::from_xyz(0.0, 10.0, 20.0).looking_at(Vec3::ZERO, Vec3::Y),
-        BlockSceneMarker,
-    ));
-    commands.spawn((
-        DirectionalLight::default(),
-        Transform::from_xyz(3.0, 3.0, 3.0).looking_at(Vec3::ZERO, Vec3::Y),
-        BlockSceneMarker,
-    ));
-    let width = current_level.0 + 2; // width starts at 3 from level 1
-    draw_blocks(commands, &Level(generation::generate_level(width)), models);
+// blocks leaving the level speed up, spin and shrink a little, and keep going past their
+// target until the camera can no longer see them
+const FLYAWAY_SPIN: f32 = 8.0;
+// turn rate around the heading for FlyawayStyle::Spin, and the pull for FlyawayStyle::Gravity
+const FLYAWAY_CORKSCREW: f32 = 20.0;
+const FLYAWAY_GRAVITY: f32 = 40.0;
+const FLYAWAY_SHRINK: f32 = 0.4;
+const FLYAWAY_TRAIL: f32 = 3.0;
+// fraction of the way to the target at which the block starts fading, it's invisible on arrival
+const FLYAWAY_FADE_START: f32 = 0.5;
+// in case the camera is zoomed out so far that the block never leaves the view
+const FLYAWAY_TIMEOUT: f32 = 2.0;
+
+fn is_offscreen(camera: &Camera, camera_transform: &GlobalTransform, position: Vec3) -> bool {
+    camera.world_to_ndc(camera_transform, position)
+        .is_none_or(|ndc| ndc.x.abs() > 1.2 || ndc.y.abs() > 1.2 || ndc.z <= 0.0)
 }
 
-fn send_block_on_click(
-    click: Trigger<Pointer<Click>>,
-    mut commands: Commands,
-    mut transforms: Query<(Entity, &mut block::Block, &mut Transform), Without<MoveDest>>,
-    level_center: Res<LevelCenter>
+fn animate_flyaway(
+    tr: &mut Transform,
+    move_dest: &MoveDest,
+    style: settings::FlyawayStyle,
+    delta: f32,
 ) {
-    let all_blocks: Vec<block::Block> = transforms.iter().map(|t| *t.1).collect();
-    let (entity_id, mut block, transform) = transforms.get_mut(click.target()).unwrap();
-    use PointerButton as P;
-    match click.event.button {
-        P::Middle => {
-            info!("block model at coords {:?}", transform.translation);
+    let heading = (move_dest.dest - move_dest.start).normalize_or_zero();
+    let t = move_dest.elapsed / move_dest.duration;
+    // a cubic ease-in leaves the target at three times the average speed, so keep that up.
+    // The target is already clear of the level, so that's where gravity starts to pull
+    let since_target = move_dest.elapsed - move_dest.duration;
+    let position = if t < 1.0 {
+        move_dest.start.lerp(move_dest.dest, EaseFunction::CubicIn.sample_clamped(t))
+    } else if style == settings::FlyawayStyle::Gravity {
+        move_dest.dest + heading * 3.0 * MoveDest::SPEED * since_target
+            - Vec3::Y * 0.5 * FLYAWAY_GRAVITY * since_target.powi(2)
+    } else {
+        move_dest.dest + heading * 3.0 * MoveDest::SPEED * since_target
+    };
+    tr.translation = position;
+    match style {
+        settings::FlyawayStyle::Spin => {
+            tr.rotate_axis(Dir3::new(heading).unwrap_or(Dir3::Y), FLYAWAY_CORKSCREW * delta);
         },
-        P::Primary => {
-            let nearest = block.get_nearest_block_in_front(all_blocks.iter().cloned());
-            let pos_opt = nearest.and_then(|b| block.move_block(&b));
-            let should_despawn = pos_opt.is_none();
-            let new_block = pos_opt.unwrap_or(get_flyaway_block_position(&block));
-            if new_block != *block {
-                commands.entity(entity_id).insert(MoveDest{ dest: new_block.get_center() - level_center.0, should_despawn });
-                *block = new_block;
-            }
+        settings::FlyawayStyle::Straight | settings::FlyawayStyle::Gravity => {
+            tr.rotate_axis(Dir3::new(heading.any_orthonormal_vector()).unwrap_or(Dir3::Y), FLYAWAY_SPIN * delta);
         },
-        _ => (),
     }
+    tr.scale = Vec3::splat(BLOCK_SCALE * (1.0 - FLYAWAY_SHRINK * t.min(1.0)));
 }
 
-fn get_flyaway_block_position(block: &block::Block) -> block::Block {
-    const EDGE: i32 = 20;
-    let block::Block { direction, min, max } = *block;
-    let size: IVec3 = block.get_isize();
-    use block::Direction as D;
-    let (new_min, new_max) = match direction {
-        D::XP => (min.with_x(EDGE - size.x), max.with_x(EDGE)),
-        D::XN => (min.with_x(-EDGE), max.with_x(-EDGE + size.x)),
-        D::YP => (min.with_y(EDGE - size.y), max.with_y(EDGE)),
-        D::YN => (min.with_y(-EDGE), max.with_y(-EDGE + size.y)),
-        D::ZP => (min.with_z(EDGE - size.z), max.with_z(EDGE)),
-        D::ZN => (min.with_z(-EDGE), max.with_z(-EDGE + size.z)),
-    };
-    block::Block { direction, min: new_min, max: new_max }
+// a sliding block closer than this to its destination is put right on it
+const SNAP_DISTANCE: f32 = 0.01;
+
+// where a sliding block is drawn: along the eased path but never past `dest`, however long the
+// frame was, and exactly on `dest` once it's within SNAP_DISTANCE
+fn slide_translation(move_dest: &MoveDest) -> Vec3 {
+    let t = (move_dest.elapsed / move_dest.duration).min(1.0);
+    let travelled = EaseFunction::CubicOut.sample_clamped(t) * move_dest.start.distance(move_dest.dest);
+    let position = move_dest.start.move_towards(move_dest.dest, travelled);
+    if position.distance(move_dest.dest) <= SNAP_DISTANCE { move_dest.dest } else { position }
 }
 
 fn animate_moving_blocks(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Transform, &block::Block, &MoveDest)>,
+    mut query: Query<(Entity, &mut Transform, &mut MoveDest)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    mut gizmos: Gizmos,
+    speed: Res<GameSpeed>,
+    settings: Res<settings::Settings>,
     time: Res<Time>,
 ) {
-    for (entity_id, mut tr, block, move_dest) in query.iter_mut() {
-        let movement_dir = block.direction.unit_vector();
-        let new_translation =
-            tr.translation + 16.0 * time.delta_secs() * movement_dir;
-        let diff = move_dest.dest - new_translation;
-        let dot = movement_dir.dot(diff);
-        let should_stop = dot < 0.0;
-        if should_stop {
-            let mut entity = commands.entity(entity_id);
-            if move_dest.should_despawn {
-                entity.despawn();
-            }
-            else {
-                *tr = tr.with_translation(move_dest.dest);
-                entity.remove::<MoveDest>();
+    let camera = camera_query.single().ok();
+    // everything is driven by the elapsed fraction of the move, so a faster game finishes the
+    // same path sooner and arrival is still detected however far a frame jumps
+    let delta = time.delta_secs() * speed.0;
+    for (entity_id, mut tr, mut move_dest) in query.iter_mut() {
+        move_dest.elapsed += delta;
+        let t = move_dest.elapsed / move_dest.duration;
+        if move_dest.should_despawn {
+            animate_flyaway(&mut tr, &move_dest, settings.flyaway_style, delta);
+            let trail_start = tr.translation.move_towards(move_dest.start, FLYAWAY_TRAIL);
+            gizmos.line_gradient(trail_start, tr.translation, Color::NONE, Color::srgba(1.0, 1.0, 1.0, 0.6));
+            let gone = camera.is_none_or(|(camera, camera_tr)| is_offscreen(camera, camera_tr, tr.translation));
+            if (t >= 1.0 && gone) || t >= 1.0 + FLYAWAY_TIMEOUT / move_dest.duration {
+                commands.entity(entity_id).despawn();
             }
         }
         else {
-            *tr = tr.with_translation(new_translation);
+            tr.translation = slide_translation(&move_dest);
+            if tr.translation == move_dest.dest {
+                commands.entity(entity_id).remove::<MoveDest>();
+            }
         }
     }
 }
 
+// also brings a block back to full opacity when an undo catches it mid-flyaway
+fn fade_flyaway_blocks(
+    query: Query<(Entity, &MoveDest)>,
+    children: Query<&Children>,
+    mesh_materials: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity_id, move_dest) in query.iter() {
+        let alpha = if move_dest.should_despawn {
+            let t = move_dest.elapsed / move_dest.duration;
+            1.0 - ((t - FLYAWAY_FADE_START) / (1.0 - FLYAWAY_FADE_START)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        set_block_alpha(entity_id, alpha, &children, &mesh_materials, &mut materials);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn undo_last_move(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    mut history: ResMut<MoveHistory>,
+    mut blocks: Query<(&mut block::Block, &mut Transform)>,
+    models: Res<BlockModels>,
+    level_center: Res<LevelCenter>,
+    mut used_undo: ResMut<achievements::UsedUndo>,
+    mut replay: ResMut<replay::Replay>,
+) {
+    let button_pressed = interaction_query.iter()
+        .any(|(i, a)| *i == Interaction::Pressed && *a == ButtonAction::UndoMove);
+    if !bindings.just_pressed(&keys, keybindings::Action::Undo) && !button_pressed {
+        return;
+    }
+    let Some(records) = history.0.pop() else { return };
+    used_undo.0 = true;
+    let moved: Vec<(block::Block, block::Block)> = records.iter().map(|(_, before, after)| (*before, *after)).collect();
+    replay.undo(&moved);
+    for (entity, before, after) in records {
+        let (entity_id, start) = match blocks.get_mut(entity) {
+            Ok((mut block, mut tr)) => {
+                *block = before;
+                // a block caught mid-flyaway is still spun and shrunk
+                tr.rotation = block_model_rotation(&before, &models).1;
+                tr.scale = Vec3::splat(BLOCK_SCALE);
+                (entity, tr.translation)
+            },
+            Err(_) => {
+                // the block flew away and was despawned, bring it back at its final position
+                let respawned = spawn_block(&mut commands, &after, &models, level_center.0);
+                commands.entity(respawned).insert(before);
+                for (e, _, _) in history.0.iter_mut().flatten() {
+                    if *e == entity {
+                        *e = respawned;
+                    }
+                }
+                (respawned, after.get_center() - level_center.0)
+            },
+        };
+        commands.entity(entity_id).insert(MoveDest::new(start, before.get_center() - level_center.0, false));
+    }
+}
+
+// a block flying away keeps its Block until it's despawned, and MoveDest is checked as well so
+// the level is only over once nothing is left in flight, whatever order systems run in
 fn finish_level_if_done(
+    blocks_query: Query<(), With<block::Block>>,
+    moving: Query<(), With<MoveDest>>,
+    mut timer: ResMut<LevelTimer>,
+    mut lstate: ResMut<NextState<LevelLoadingState>>,
+) {
+    if blocks_query.is_empty() && moving.is_empty() {
+        timer.running = false;
+        lstate.set(LevelLoadingState::Cleared);
+    }
+}
+
+// replacing the task drops the one for the previous level, which cancels it
+fn start_optimal_moves(initial_level: Res<InitialLevel>, mut optimal: ResMut<OptimalMoves>) {
+    let blocks = initial_level.0.clone();
+    let (lower, upper) = Level(blocks.clone()).bounds();
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        generation::solve(&blocks, (lower.as_ivec3(), upper.as_ivec3())).map(|s| s.len() as u32)
+    });
+    *optimal = OptimalMoves { task: Some(task), moves: None };
+}
+
+fn poll_optimal_moves(mut optimal: ResMut<OptimalMoves>) {
+    let Some(task) = &mut optimal.task else { return };
+    let finished = block_on(future::poll_once(task));
+    if let Some(moves) = finished {
+        *optimal = OptimalMoves { task: None, moves };
+    }
+}
+
+// how long the cleared level stays on screen before moving on
+const CLEARED_SECS: f32 = 1.2;
+
+#[derive(Resource)]
+pub struct ClearedTimer(Timer);
+
+#[derive(Component)]
+pub struct ClearedFlash;
+
+fn start_cleared_celebration(mut commands: Commands) {
+    commands.insert_resource(ClearedTimer(Timer::from_seconds(CLEARED_SECS, TimerMode::Once)));
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        BackgroundColor(Color::WHITE.with_alpha(0.6)),
+        ClearedFlash,
+        BlockSceneMarker,
+    ));
+}
+
+// a fading flash and the empty bounding box pulsing, then the usual way out of the level.
+// Gameplay systems don't run outside LevelLoadingState::Level, so input is ignored meanwhile
+#[allow(clippy::too_many_arguments)]
+fn celebrate_level_cleared(
     mut commands: Commands,
+    mut cleared: ResMut<ClearedTimer>,
+    mut flash_query: Query<&mut BackgroundColor, With<ClearedFlash>>,
+    mut gizmos: Gizmos,
+    level_bounds: Res<LevelBounds>,
+    level_center: Res<LevelCenter>,
     scene_query: Query<Entity, With<BlockSceneMarker>>,
-    blocks_query: Query<&block::Block>,
+    current_level: Res<CurrentLevel>,
+    mut istate: ResMut<NextState<Interface>>,
+    move_count: Res<MoveCount>,
+    timer: Res<LevelTimer>,
+    edited_level: Option<Res<editor::EditedLevel>>,
+    mut completed: EventWriter<LevelCompleted>,
+    time: Res<Time>,
+) {
+    cleared.0.tick(time.delta());
+    let t = cleared.0.fraction();
+    for mut flash in flash_query.iter_mut() {
+        flash.0 = Color::WHITE.with_alpha(0.6 * (1.0 - t).powi(2));
+    }
+    let LevelBounds(lower, upper) = *level_bounds;
+    let (lower, upper) = (lower.as_vec3(), upper.as_vec3());
+    let pulse = 1.0 + 0.08 * (t * std::f32::consts::TAU * 3.0).sin();
+    gizmos.cuboid(
+        Transform::from_translation(lower.midpoint(upper) - level_center.0).with_scale((upper - lower) * pulse),
+        Color::srgb(1.0, 0.85, 0.1).with_alpha(1.0 - t),
+    );
+    if !cleared.0.finished() {
+        return;
+    }
+    commands.remove_resource::<ClearedTimer>();
+    scene_query.iter().for_each(|e| commands.entity(e).despawn());
+    // testing an edited level doesn't count towards progress
+    if edited_level.is_some() {
+        istate.set(Interface::Editor);
+        return;
+    }
+    completed.write(LevelCompleted {
+        level: current_level.0,
+        moves: move_count.0,
+        time: timer.elapsed,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_level_completion(
+    mut commands: Commands,
+    mut completed: EventReader<LevelCompleted>,
     mut next_level: ResMut<CurrentLevel>,
     mut istate: ResMut<NextState<Interface>>,
+    mut best_times: ResMut<BestTimes>,
+    mut max_unlocked: ResMut<MaxUnlocked>,
+    mut total_score: ResMut<Score>,
+    initial_level: Res<InitialLevel>,
+    optimal_moves: Res<OptimalMoves>,
+    got_stuck: Res<GotStuck>,
+    daily: Option<Res<DailyChallenge>>,
+    practice: Option<Res<PracticeLevel>>,
+    play_seed: Option<Res<seed_entry::PlaySeed>>,
+    mut daily_bests: ResMut<DailyBests>,
+    mut unlocked: ResMut<achievements::Achievements>,
+    used_undo: Res<achievements::UsedUndo>,
 ) {
-    if blocks_query.iter().count() == 0 {
-        scene_query.iter().for_each(|e| commands.entity(e).despawn());
-        let current_level = next_level.0;
-        *next_level = CurrentLevel(current_level + 1);
+    for &LevelCompleted { level, moves, time } in completed.read() {
+        if practice.is_some() || play_seed.is_some() {
+            commands.remove_resource::<PracticeLevel>();
+            commands.remove_resource::<seed_entry::PlaySeed>();
+            istate.set(Interface::Menu);
+            continue;
+        }
+        // the daily challenge only competes against earlier attempts on the same day
+        if let Some(daily) = &daily {
+            if daily_bests.0.get(&daily.date).is_none_or(|best| time < *best) {
+                daily_bests.0.insert(daily.date, time);
+            }
+            save::store(&save::SaveData {
+                current_level: next_level.0,
+                max_unlocked: max_unlocked.0,
+                best_times: best_times.0.clone(),
+                total_score: total_score.0,
+                daily_bests: daily_bests.0.clone(),
+                achievements: unlocked.0.clone(),
+            });
+            commands.remove_resource::<DailyChallenge>();
+            commands.remove_resource::<LevelCompletion>();
+            istate.set(Interface::Menu);
+            continue;
+        }
+        max_unlocked.0 = max_unlocked.0.max(level.saturating_add(1));
+        let new_record = best_times.0.get(&level).is_none_or(|best| time < *best);
+        if new_record {
+            best_times.0.insert(level, time);
+        }
+        let (lower, upper) = Level(initial_level.0.clone()).bounds();
+        // a level cleared before the solver is done scores against its block count instead
+        let optimal = optimal_moves.moves;
+        let summary = achievements::CompletionSummary {
+            moves,
+            optimal,
+            used_undo: used_undo.0,
+            side_len: achievements::side_len(lower, upper),
+            levels_cleared: best_times.0.len(),
+        };
+        for (row, achievement) in achievements::unlock(&mut unlocked, &summary).into_iter().enumerate() {
+            achievements::show_toast(&mut commands, achievement, row);
+        }
+        let bonus = if got_stuck.0 { 0 } else { score::NOT_STUCK_BONUS };
+        let score = score::level_score(moves, time, optimal.unwrap_or(initial_level.0.len() as u32)) + bonus;
+        total_score.0 += score;
+        save::store(&save::SaveData {
+            current_level: level.saturating_add(1),
+            max_unlocked: max_unlocked.0,
+            best_times: best_times.0.clone(),
+            total_score: total_score.0,
+            daily_bests: daily_bests.0.clone(),
+            achievements: unlocked.0.clone(),
+        });
+        commands.insert_resource(LevelCompletion { level, moves, time, new_record, score, optimal });
+        *next_level = CurrentLevel(level.saturating_add(1));
         istate.set(Interface::Menu);
     }
 }
@@ -232,17 +1744,72 @@ enum Interface {
     #[default]
     Menu,
     Gameplay,
+    Paused,
+    LevelSelect,
+    Editor,
+    Settings,
+    SeedEntry,
+}
+
+// pausing keeps the loaded level around, so the level state survives it
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, SubStates)]
+#[source(Interface = Interface::Gameplay | Interface::Paused)]
+enum LevelLoadingState {
+    #[default]
+    Loading,
+    Level,
+    // every block is gone, the level lingers for a moment before it's recorded
+    Cleared,
 }
 
+// systems that only run while a loaded level is being played
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct PlayingSet;
+
 #[derive(Resource)]
 struct CurrentLevel(u8);
 
+#[derive(Resource)]
+struct MaxUnlocked(u8);
+
+#[derive(Resource, Default)]
+struct CurrentDifficulty(generation::Difficulty);
+
 #[derive(Component)]
 struct MenuMarker;
 
-fn text(level: u8) -> impl Bundle {
+#[derive(Component)]
+struct PauseMenuMarker;
+
+#[derive(Component)]
+struct LevelSelectMarker;
+
+#[derive(Component)]
+struct SettingsMarker;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum ButtonAction {
+    StartPlaying,
+    Regenerate,
+    PlayEndless,
+    PlayDaily,
+    ResetLevel,
+    Resume,
+    BackToMenu,
+    SelectLevel,
+    EnterSeed,
+    PlayLevel(u8),
+    SetDifficulty(generation::Difficulty),
+    UndoMove,
+    OpenEditor,
+    TestLevel,
+    SaveLevel,
+    OpenSettings,
+}
+
+fn text(content: String) -> impl Bundle {
     (
-        Text::new(format!("Next: Level {}", level)),
+        Text::new(content),
         TextFont {
             font_size: 33.0,
             ..default()
@@ -252,11 +1819,16 @@ fn text(level: u8) -> impl Bundle {
     )
 }
 
-fn button() -> impl Bundle {
+fn button(label: &str, action: ButtonAction) -> impl Bundle {
+    sized_button(label, action, Val::Px(300.0))
+}
+
+fn sized_button(label: &str, action: ButtonAction, width: Val) -> impl Bundle {
     (
         Button,
+        action,
         Node {
-            width: Val::Px(300.0),
+            width,
             height: Val::Px(65.0),
             border: UiRect::all(Val::Px(5.0)),
             // horizontally center child text
@@ -269,7 +1841,7 @@ fn button() -> impl Bundle {
         BorderRadius::MAX,
         BackgroundColor(Color::BLACK),
         children![(
-            Text::new("Start playing"),
+            Text::new(label),
             TextFont {
                 font_size: 33.0,
                 ..default()
@@ -280,7 +1852,22 @@ fn button() -> impl Bundle {
     )
 }
 
-fn draw_menu(level: u8) -> impl Bundle {
+fn draw_menu(level: u8, completion: Option<&LevelCompletion>, total_score: u32, daily_best: Option<f32>) -> impl Bundle {
+    let mut heading = match completion {
+        Some(c) => format!(
+            "Level {} cleared in {} moves{} ({:.1} s), +{} points{}\nScore: {}\nNext: Level {}",
+            c.level, c.moves,
+            c.optimal.map_or(String::new(), |optimal| format!(" (optimal: {})", optimal)),
+            c.time, c.score,
+            if c.new_record { "\nNew record!" } else { "" },
+            total_score,
+            level,
+        ),
+        None => format!("Score: {}\nNext: Level {}", total_score, level),
+    };
+    if let Some(best) = daily_best {
+        heading.push_str(&format!("\nToday's daily challenge: {:.1} s", best));
+    }
     (
         Node {
             width: Val::Percent(100.0),
@@ -294,32 +1881,352 @@ fn draw_menu(level: u8) -> impl Bundle {
         },
         // TabGroup::default(),
         children![
-            text(level),
-            button(),
+            text(heading),
+            button("Start playing", ButtonAction::StartPlaying),
+            button("Regenerate", ButtonAction::Regenerate),
+            button("Endless", ButtonAction::PlayEndless),
+            button("Daily challenge", ButtonAction::PlayDaily),
+            button("Select level", ButtonAction::SelectLevel),
+            button("Play a seed", ButtonAction::EnterSeed),
+            (
+                Node {
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                },
+                Children::spawn(SpawnIter(generation::Difficulty::ALL.into_iter().map(|d| {
+                    sized_button(d.name(), ButtonAction::SetDifficulty(d), Val::Px(96.0))
+                }))),
+            ),
+            button("Level editor", ButtonAction::OpenEditor),
+            button("Settings", ButtonAction::OpenSettings),
         ],
     )
 }
 
 fn button_system(
     mut commands: Commands,
-    interaction_query: Query<&Interaction, Changed<Interaction>>,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
     menu_elements_query: Query<Entity, With<MenuMarker>>,
+    mut difficulty: ResMut<CurrentDifficulty>,
+    current_level: Res<CurrentLevel>,
+    completion: Option<Res<LevelCompletion>>,
+    mut istate: ResMut<NextState<Interface>>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        match (*interaction, *action) {
+            (Interaction::Pressed, ButtonAction::SetDifficulty(chosen)) => difficulty.0 = chosen,
+            (Interaction::Pressed, ButtonAction::StartPlaying) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                istate.set(Interface::Gameplay);
+            },
+            // another level the size of the one just cleared, or of the next one before any was
+            (Interaction::Pressed, ButtonAction::Regenerate) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                let level = completion.as_deref().map_or(current_level.0, |c| c.level);
+                commands.insert_resource(PracticeLevel(level));
+                istate.set(Interface::Gameplay);
+            },
+            (Interaction::Pressed, ButtonAction::PlayEndless) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                commands.init_resource::<EndlessMode>();
+                istate.set(Interface::Gameplay);
+            },
+            (Interaction::Pressed, ButtonAction::PlayDaily) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                commands.insert_resource(DailyChallenge { date: daily::today_utc() });
+                istate.set(Interface::Gameplay);
+            },
+            (Interaction::Pressed, ButtonAction::SelectLevel) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                istate.set(Interface::LevelSelect);
+            },
+            (Interaction::Pressed, ButtonAction::EnterSeed) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                istate.set(Interface::SeedEntry);
+            },
+            (Interaction::Pressed, ButtonAction::OpenEditor) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                istate.set(Interface::Editor);
+            },
+            (Interaction::Pressed, ButtonAction::OpenSettings) => {
+                menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
+                istate.set(Interface::Settings);
+            },
+            _ => (),
+        }
+    }
+}
+
+fn highlight_chosen_difficulty(
+    difficulty: Res<CurrentDifficulty>,
+    mut buttons: Query<(&ButtonAction, &mut BackgroundColor)>,
+) {
+    for (action, mut background) in buttons.iter_mut() {
+        if let ButtonAction::SetDifficulty(d) = action {
+            *background = if *d == difficulty.0 { Color::srgb(0.2, 0.2, 0.55) } else { Color::BLACK }.into();
+        }
+    }
+}
+
+fn setup_level_select(
+    mut commands: Commands,
+    max_unlocked: Res<MaxUnlocked>,
+) {
+    commands.spawn((Camera2d, LevelSelectMarker));
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        LevelSelectMarker,
+    ))
+    .with_children(|parent| {
+        parent.spawn(text(String::from("Select level")));
+        parent.spawn(Node {
+            display: Display::Grid,
+            grid_template_columns: RepeatedGridTrack::px(5, 100.0),
+            row_gap: Val::Px(10.0),
+            column_gap: Val::Px(10.0),
+            ..default()
+        })
+        .with_children(|grid| {
+            for level in 1..=max_unlocked.0 {
+                grid.spawn(sized_button(&level.to_string(), ButtonAction::PlayLevel(level), Val::Px(100.0)));
+            }
+        });
+        parent.spawn(button("Back", ButtonAction::BackToMenu));
+    });
+}
+
+fn level_select_button_system(
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut istate: ResMut<NextState<Interface>>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        match (*interaction, *action) {
+            (Interaction::Pressed, ButtonAction::PlayLevel(level)) => {
+                *current_level = CurrentLevel(level);
+                istate.set(Interface::Gameplay);
+            },
+            (Interaction::Pressed, ButtonAction::BackToMenu) => istate.set(Interface::Menu),
+            _ => (),
+        }
+    }
+}
+
+fn despawn_level_select(
+    mut commands: Commands,
+    level_select_query: Query<Entity, With<LevelSelectMarker>>,
+) {
+    level_select_query.iter().for_each(|e| commands.entity(e).despawn());
+}
+
+fn setup_settings(mut commands: Commands) {
+    commands.spawn((Camera2d, bevy_egui::PrimaryEguiContext, SettingsMarker));
+}
+
+// a window instead of a full-screen panel, so egui only takes the pointer while it's over it.
+// Widgets write straight into the resources; the change is only flagged when a widget was
+// actually changed, so systems listening for settings changes don't run every frame.
+// Clicking a key binding waits for the next key press and binds that
+#[allow(clippy::too_many_arguments)]
+fn draw_settings(
+    mut contexts: bevy_egui::EguiContexts,
+    mut settings: ResMut<settings::Settings>,
+    mut show_bounds: ResMut<overlay::ShowBounds>,
+    mut show_gizmos: ResMut<overlay::ShowDirectionGizmos>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut bindings: ResMut<keybindings::KeyBindings>,
+    mut rebinding: Local<Option<keybindings::Action>>,
+    mut refused: Local<Option<KeyCode>>,
+    mut istate: ResMut<NextState<Interface>>,
+) -> Result {
+    use bevy_egui::egui;
+    if let Some(action) = *rebinding {
+        if let Some(key) = keys.get_just_pressed().next() {
+            // a reserved key keeps the button waiting, saying why
+            if bindings.bind(action, *key) {
+                *rebinding = None;
+                *refused = None;
+            } else {
+                *refused = Some(*key);
+            }
+        }
+    }
+    let mut changed = false;
+    egui::Window::new("Settings")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            let s = settings.bypass_change_detection();
+            changed |= ui.add(egui::Slider::new(&mut s.music_volume, 0.0..=1.0).text("Music")).changed();
+            changed |= ui.checkbox(&mut s.music_muted, "Mute music").changed();
+            changed |= ui.add(egui::Slider::new(&mut s.sfx_volume, 0.0..=1.0).text("Effects")).changed();
+            changed |= ui.add(
+                egui::Slider::new(&mut s.game_speed, settings::Settings::MIN_GAME_SPEED..=settings::Settings::MAX_GAME_SPEED)
+                    .step_by(0.25)
+                    .suffix("x")
+                    .text("Game speed"),
+            ).changed();
+            ui.horizontal(|ui| {
+                ui.label("Flyaway");
+                for style in settings::FlyawayStyle::ALL {
+                    changed |= ui.radio_value(&mut s.flyaway_style, style, style.name()).changed();
+                }
+            });
+            ui.separator();
+            let sensitivity = settings::Settings::MIN_SENSITIVITY..=settings::Settings::MAX_SENSITIVITY;
+            changed |= ui.add(egui::Slider::new(&mut s.orbit_sensitivity, sensitivity.clone()).text("Orbit sensitivity")).changed();
+            changed |= ui.add(egui::Slider::new(&mut s.pan_sensitivity, sensitivity.clone()).text("Pan sensitivity")).changed();
+            changed |= ui.add(egui::Slider::new(&mut s.zoom_sensitivity, sensitivity).text("Zoom sensitivity")).changed();
+            changed |= ui.checkbox(&mut s.invert_y, "Invert vertical orbit").changed();
+            changed |= ui.checkbox(&mut s.auto_focus_on_move, "Follow moved blocks").changed();
+            ui.separator();
+            ui.checkbox(&mut show_bounds.0, "Show level bounds");
+            ui.checkbox(&mut show_gizmos.0, "Show block directions");
+            changed |= ui.checkbox(&mut s.show_ground, "Show ground and shadows").changed();
+            changed |= ui.add(
+                egui::Slider::new(&mut s.ambient_brightness, settings::Settings::MIN_AMBIENT..=settings::Settings::MAX_AMBIENT)
+                    .text("Ambient light"),
+            ).changed();
+            ui.separator();
+            ui.collapsing("Keys", |ui| {
+                egui::Grid::new("key_bindings").num_columns(2).show(ui, |ui| {
+                    for action in keybindings::Action::ALL {
+                        ui.label(action.name());
+                        let label = if *rebinding == Some(action) {
+                            match *refused {
+                                Some(key) => format!("{} is taken, press another", keybindings::key_label(key)),
+                                None => String::from("Press a key"),
+                            }
+                        } else {
+                            keybindings::key_label(bindings.key(action))
+                        };
+                        if ui.button(label).clicked() {
+                            *rebinding = if *rebinding == Some(action) { None } else { Some(action) };
+                            *refused = None;
+                        }
+                        ui.end_row();
+                    }
+                });
+                if ui.button("Default keys").clicked() {
+                    *bindings = keybindings::KeyBindings::default();
+                    *rebinding = None;
+                    *refused = None;
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    s.show_bounds = show_bounds.0;
+                    s.show_direction_gizmos = show_gizmos.0;
+                    settings::store(s);
+                    keybindings::store(&bindings);
+                }
+                if ui.button("Back").clicked() {
+                    istate.set(Interface::Menu);
+                }
+            });
+        });
+    if changed {
+        settings.set_changed();
+    }
+    Ok(())
+}
+
+fn despawn_settings(
+    mut commands: Commands,
+    settings_query: Query<Entity, With<SettingsMarker>>,
+) {
+    settings_query.iter().for_each(|e| commands.entity(e).despawn());
+}
+
+fn toggle_pause(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    gamepads: Query<&Gamepad>,
+    interface: Res<State<Interface>>,
+    mut istate: ResMut<NextState<Interface>>,
+) {
+    if bindings.just_pressed(&keys, keybindings::Action::Pause) || gamepad::gamepad_pause_pressed(&gamepads) {
+        match interface.get() {
+            Interface::Gameplay => istate.set(Interface::Paused),
+            Interface::Paused => istate.set(Interface::Gameplay),
+            Interface::Menu | Interface::LevelSelect | Interface::Editor | Interface::Settings | Interface::SeedEntry => (),
+        }
+    }
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        children![
+            text(String::from("Paused")),
+            button("Resume", ButtonAction::Resume),
+            button("Back to menu", ButtonAction::BackToMenu),
+        ],
+        PauseMenuMarker,
+    ));
+}
+
+fn pause_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &ButtonAction), Changed<Interaction>>,
+    scene_query: Query<Entity, With<BlockSceneMarker>>,
     mut istate: ResMut<NextState<Interface>>,
 ) {
-    for interaction in interaction_query.iter() {
-        if let Interaction::Pressed = *interaction {
-            menu_elements_query.iter().for_each(|e| commands.entity(e).despawn());
-            istate.set(Interface::Gameplay);
+    for (interaction, action) in interaction_query.iter() {
+        match (*interaction, *action) {
+            (Interaction::Pressed, ButtonAction::Resume) => istate.set(Interface::Gameplay),
+            (Interaction::Pressed, ButtonAction::BackToMenu) => {
+                scene_query.iter().for_each(|e| commands.entity(e).despawn());
+                commands.remove_resource::<editor::EditedLevel>();
+                commands.remove_resource::<EndlessMode>();
+                commands.remove_resource::<DailyChallenge>();
+                commands.remove_resource::<PracticeLevel>();
+                commands.remove_resource::<seed_entry::PlaySeed>();
+                istate.set(Interface::Menu);
+            },
+            _ => (),
         }
     }
 }
 
+fn despawn_pause_menu(
+    mut commands: Commands,
+    pause_menu_query: Query<Entity, With<PauseMenuMarker>>,
+) {
+    pause_menu_query.iter().for_each(|e| commands.entity(e).despawn());
+}
+
 fn setup_menu(
     mut commands: Commands,
     level: Res<CurrentLevel>,
+    completion: Option<Res<LevelCompletion>>,
+    total_score: Res<Score>,
+    daily_bests: Res<DailyBests>,
 ) {
+    let daily_best = daily_bests.0.get(&daily::today_utc()).copied();
     commands.spawn((Camera2d, MenuMarker));
-    commands.spawn((draw_menu(level.0), MenuMarker));
+    commands.spawn((draw_menu(level.0, completion.as_deref(), total_score.0, daily_best), MenuMarker));
 }
 
 fn main() {
@@ -327,7 +2234,10 @@ fn main() {
       title: String::from("Clear Cube"),
       ..default()
     });
-    App::new()
+    let save_data = save::load();
+    let settings = settings::load();
+    let mut app = App::new();
+    app
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
                 primary_window: app_window,
@@ -335,15 +2245,405 @@ fn main() {
             }),
             MeshPickingPlugin,
             PanOrbitCameraPlugin,
+            bevy_egui::EguiPlugin::default(),
+            editor::EditorPlugin,
         ))
-        .insert_resource(CurrentLevel(1))
+        // cameras come and go with each screen, so the egui context lives on the gameplay camera
+        // instead of being attached once to whichever camera spawns first
+        .insert_resource(bevy_egui::EguiGlobalSettings {
+            auto_create_primary_context: false,
+            ..default()
+        })
+        .insert_resource(CurrentLevel(save_data.current_level))
+        .insert_resource(MaxUnlocked(save_data.max_unlocked))
+        .insert_resource(BestTimes(save_data.best_times))
+        .insert_resource(DailyBests(save_data.daily_bests))
+        .insert_resource(Score(save_data.total_score))
+        .insert_resource(achievements::Achievements(save_data.achievements))
+        .init_resource::<achievements::UsedUndo>()
+        .init_resource::<replay::Replay>()
+        .init_resource::<GotStuck>()
+        .init_resource::<MoveCount>()
+        .init_resource::<CurrentDifficulty>()
+        .insert_resource(overlay::ShowBounds(settings.show_bounds))
+        .insert_resource(overlay::ShowDirectionGizmos(settings.show_direction_gizmos))
+        .insert_resource(settings)
+        .insert_resource(keybindings::load())
+        .init_resource::<HoveredBlock>()
+        .init_resource::<PressPosition>()
+        .init_resource::<drag::BlockDrag>()
+        .init_resource::<Solution>()
+        .init_resource::<OptimalMoves>()
+        .init_resource::<block::BlockIndex>()
+        .init_resource::<FreeBlocks>()
+        .init_resource::<AnimationInProgress>()
+        .init_resource::<GameSpeed>()
+        .init_resource::<camera::PitchBeforeOrbit>()
+        .init_resource::<selection::Selected>()
+        .init_resource::<seed_entry::SeedForm>()
+        .init_resource::<overlay::ShowLockedBlocks>()
+        .init_resource::<minimap::MinimapAxis>()
+        .init_resource::<block_info::ShowBlockInfo>()
+        .add_event::<BlockActivated>()
+        .add_event::<LevelCompleted>()
+        .init_resource::<LevelTimer>()
+        .init_asset::<Level>()
+        .init_asset_loader::<LevelLoader>()
         .init_state::<Interface>()
+        .add_sub_state::<LevelLoadingState>()
         .add_systems(OnEnter(Interface::Menu), setup_menu)
-        .add_systems(Update, button_system.run_if(in_state(Interface::Menu)))
-        .add_systems(OnEnter(Interface::Gameplay), setup_level)
-        .add_systems(Update, animate_moving_blocks.run_if(in_state(Interface::Gameplay)))
-        .add_systems(Update, finish_level_if_done.run_if(in_state(Interface::Gameplay)))
+        .add_systems(Update, (button_system, highlight_chosen_difficulty).run_if(in_state(Interface::Menu)))
+        .configure_sets(Update, PlayingSet
+            .run_if(in_state(Interface::Gameplay))
+            .run_if(in_state(LevelLoadingState::Level)))
+        .add_systems(OnEnter(LevelLoadingState::Loading), setup_level)
+        .add_systems(OnEnter(LevelLoadingState::Level), camera::frame_camera_to_level)
+        .add_systems(Update, camera::reframe_on_key.in_set(PlayingSet))
+        .add_systems(Update, camera::return_camera_home.in_set(PlayingSet).run_if(resource_exists::<camera::CameraHome>))
+        .add_systems(Update, camera::toggle_orthographic.in_set(PlayingSet).run_if(resource_exists::<camera::CameraHome>))
+        .add_systems(Update, camera::snap_camera_to_axis.in_set(PlayingSet).run_if(resource_exists::<camera::CameraHome>))
+        .add_systems(Update, camera::focus_on_moved_block.in_set(PlayingSet).run_if(resource_exists::<camera::CameraHome>))
+        .add_systems(Update, (overlay::toggle_bounds, overlay::draw_level_bounds).in_set(PlayingSet))
+        .add_systems(Update, (
+            selection::keep_selection_valid,
+            selection::move_selection,
+            selection::activate_selected_on_enter.before(move_activated_blocks),
+            selection::draw_selection,
+        ).chain().in_set(PlayingSet))
+        .add_systems(Update, (
+            gamepad::gamepad_move_selection.after(selection::keep_selection_valid),
+            gamepad::gamepad_activate_selected.before(move_activated_blocks),
+            gamepad::gamepad_orbit_camera,
+        ).in_set(PlayingSet))
+        .add_systems(Update, (overlay::toggle_direction_gizmos, overlay::draw_direction_gizmos).in_set(PlayingSet))
+        .add_systems(Update, (
+            cutaway::toggle_cutaway,
+            (cutaway::adjust_cutaway, cutaway::draw_cutaway_plane).chain().run_if(resource_exists::<cutaway::CutawayPlane>),
+            cutaway::apply_cutaway,
+        ).chain().in_set(PlayingSet))
+        .add_systems(bevy_egui::EguiPrimaryContextPass, (minimap::draw_minimap, block_info::draw_block_info)
+            .run_if(in_state(Interface::Gameplay))
+            .run_if(in_state(LevelLoadingState::Level)))
+        .add_systems(Update, block_info::toggle_block_info.in_set(PlayingSet))
+        .add_systems(Update, (
+            drag::drop_released_block,
+            drag::lock_camera_while_dragging.run_if(resource_changed::<drag::BlockDrag>),
+        ).chain().in_set(PlayingSet))
+        .add_systems(Update, toggle_pause
+            .run_if(in_state(Interface::Gameplay).or(in_state(Interface::Paused)))
+            .run_if(not(in_state(LevelLoadingState::Cleared))))
+        .add_systems(OnEnter(Interface::LevelSelect), setup_level_select)
+        .add_systems(OnExit(Interface::LevelSelect), despawn_level_select)
+        .add_systems(Update, level_select_button_system.run_if(in_state(Interface::LevelSelect)))
+        .add_systems(OnEnter(Interface::Paused), (setup_pause_menu, audio::pause_music))
+        .add_systems(OnExit(Interface::Paused), (despawn_pause_menu, audio::resume_music))
+        .add_systems(OnEnter(Interface::Settings), setup_settings)
+        .add_systems(OnExit(Interface::Settings), despawn_settings)
+        .add_systems(bevy_egui::EguiPrimaryContextPass, draw_settings.run_if(in_state(Interface::Settings)))
+        .add_systems(OnEnter(Interface::SeedEntry), seed_entry::setup_seed_entry)
+        .add_systems(OnExit(Interface::SeedEntry), seed_entry::despawn_seed_entry)
+        .add_systems(bevy_egui::EguiPrimaryContextPass, seed_entry::draw_seed_entry.run_if(in_state(Interface::SeedEntry)))
+        .add_systems(Startup, (load_block_models, decal::create_decal_mesh, audio::spawn_music))
+        .add_systems(Update, audio::apply_music_volume.run_if(resource_changed::<settings::Settings>))
+        .add_systems(Update, apply_game_speed.run_if(resource_changed::<settings::Settings>))
+        .add_systems(Update, camera::apply_camera_settings)
+        .add_systems(PostUpdate, (
+            camera::remember_pitch.before(bevy_panorbit_camera::PanOrbitCameraSystemSet),
+            camera::invert_orbit_pitch.after(bevy_panorbit_camera::PanOrbitCameraSystemSet),
+        ))
+        .add_systems(Update, pause_button_system.run_if(in_state(Interface::Paused)))
+        .add_systems(Update, wait_for_level_asset.run_if(in_state(LevelLoadingState::Loading)).run_if(resource_exists::<LevelHandle>))
+        .add_systems(Update, auto_solve.before(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, (
+            replay::start_playback,
+            replay::drive_playback.run_if(resource_exists::<replay::ReplayPlayback>),
+        ).chain().before(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, rebuild_block_index.before(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, move_activated_blocks.before(animate_moving_blocks).in_set(PlayingSet))
+        .add_systems(Update, animate_moving_blocks.in_set(PlayingSet))
+        .add_systems(Update, fade_flyaway_blocks.after(animate_moving_blocks).in_set(PlayingSet))
+        .add_systems(Update, (popup::spawn_exit_popups, popup::animate_popups).chain().after(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, animate_spawning_blocks.in_set(PlayingSet))
+        .add_systems(Update, undo_last_move.before(finish_level_if_done).in_set(PlayingSet))
+        .add_systems(Update, update_animation_in_progress
+            .after(move_activated_blocks)
+            .after(animate_moving_blocks)
+            .after(animate_spawning_blocks)
+            .in_set(PlayingSet))
+        .add_systems(Update, finish_level_if_done
+            .after(update_animation_in_progress)
+            .in_set(PlayingSet)
+            .run_if(not(resource_exists::<EndlessMode>))
+            .run_if(not(animation_in_progress)))
+        .add_systems(Update, continue_endless.in_set(PlayingSet).run_if(resource_exists::<EndlessMode>))
+        .add_systems(Update, camera::frame_camera_to_level.run_if(resource_changed::<LevelBounds>).in_set(PlayingSet))
+        .add_systems(Update, ground::place_ground_plane.run_if(resource_changed::<LevelBounds>).in_set(PlayingSet))
+        .add_systems(Update, ground::apply_ground_setting.run_if(resource_changed::<settings::Settings>))
+        .add_systems(Update, lighting::apply_ambient_setting.run_if(resource_changed::<settings::Settings>))
+        .add_systems(Update, update_free_blocks.after(animate_moving_blocks).in_set(PlayingSet))
+        .add_systems(Update, detect_stuck.after(update_free_blocks).after(update_animation_in_progress).in_set(PlayingSet))
+        .add_systems(OnEnter(LevelLoadingState::Cleared), start_cleared_celebration)
+        .add_systems(Update, celebrate_level_cleared
+            .run_if(in_state(Interface::Gameplay))
+            .run_if(in_state(LevelLoadingState::Cleared))
+            .run_if(resource_exists::<ClearedTimer>))
+        .add_systems(Update, (
+            start_optimal_moves.run_if(resource_exists_and_changed::<InitialLevel>),
+            poll_optimal_moves,
+        ).chain().before(record_level_completion))
+        .add_systems(Update, record_level_completion.after(celebrate_level_cleared))
+        .add_systems(Update, achievements::expire_toasts)
+        .add_systems(Update, reset_level.in_set(PlayingSet))
+        .add_systems(Update, reshuffle_level.before(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, (rotate_level, mirror_level).chain().before(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, export_level.in_set(PlayingSet))
+        .add_systems(Update, show_hint.after(update_animation_in_progress).in_set(PlayingSet).run_if(not(animation_in_progress)))
+        .add_systems(Update, pulse_hinted_blocks.in_set(PlayingSet))
+        .add_systems(Update, clear_hint_text.run_if(resource_changed::<MoveCount>).run_if(in_state(Interface::Gameplay)))
+        .add_systems(Update, update_ghost.after(rebuild_block_index).in_set(PlayingSet))
+        .add_systems(Update, tick_level_timer.in_set(PlayingSet))
+        .add_systems(Update, update_blocks_left_text.after(move_activated_blocks).in_set(PlayingSet))
+        .add_systems(Update, tutorial::update_tutorial_prompt
+            .run_if(resource_changed::<MoveCount>)
+            .run_if(resource_exists::<tutorial::TutorialPrompts>)
+            .run_if(in_state(Interface::Gameplay)))
+        .add_systems(Update, update_move_count_text.run_if(resource_changed::<MoveCount>).run_if(in_state(Interface::Gameplay)))
         .register_type::<MoveDest>()
         .register_type::<block::Block>()
-        .run();
+        .register_type::<LevelSeed>();
+    // the inspector and debug panels, see dev::DevPlugin
+    #[cfg(feature = "dev")]
+    app.add_plugins(dev::DevPlugin);
+    app.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn level_round_trips_through_json() {
+        let level = Level(vec![
+            block::Block::new(block::Direction::XP, IVec3::ZERO, IVec3::ONE),
+            block::Block::new(block::Direction::ZN, IVec3::new(1, 0, 0), IVec3::new(2, 1, 3)),
+            block::Block { step: 2, ..block::Block::new(block::Direction::YN, IVec3::new(0, 1, 0), IVec3::new(1, 2, 1)) },
+        ]);
+        let json = serde_json::to_string_pretty(&level).unwrap();
+        assert_eq!(serde_json::from_str::<Level>(&json).unwrap(), level);
+    }
+
+    fn test_models() -> BlockModels {
+        BlockModels {
+            small_model: Handle::weak_from_u128(1),
+            wide_model: Handle::weak_from_u128(2),
+            long_model: Handle::weak_from_u128(3),
+            very_wide_model: Handle::weak_from_u128(4),
+            very_long_model: Handle::weak_from_u128(5),
+        }
+    }
+
+    // the models point up along Y; the long ones are elongated along Y too, the wide ones along Z
+    #[test]
+    fn models_put_the_long_side_on_the_elongation_axis() {
+        let models = test_models();
+        for direction in block::Direction::ALL {
+            let (model, rotation) = block_model_rotation(&block::Block::new(direction, IVec3::ZERO, IVec3::ONE), &models);
+            assert_eq!(model, models.small_model);
+            assert!(rotation.mul_vec3(Vec3::Y).abs_diff_eq(direction.unit_vector(), 1e-5));
+            for elongation in block::Axis::ALL {
+                for length in 2..=block::Block::MAX_LENGTH {
+                    let size = IVec3::ONE + elongation.set_ivec3_component(&IVec3::ZERO, length - 1);
+                    let b = block::Block::new(direction, IVec3::ZERO, size);
+                    let (model, rotation) = block_model_rotation(&b, &models);
+                    let long = elongation == direction.axis;
+                    let expected = match (long, length) {
+                        (true, 2) => &models.long_model,
+                        (true, _) => &models.very_long_model,
+                        (false, 2) => &models.wide_model,
+                        (false, _) => &models.very_wide_model,
+                    };
+                    assert_eq!(&model, expected, "{:?} elongated along {:?}", direction, elongation);
+                    assert!(rotation.mul_vec3(Vec3::Y).abs_diff_eq(direction.unit_vector(), 1e-5),
+                        "{:?} elongated along {:?} points along {}", direction, elongation, rotation.mul_vec3(Vec3::Y));
+                    let long_side = rotation.mul_vec3(if long { Vec3::Y } else { Vec3::Z });
+                    assert!(elongation.vec3_component(long_side).abs() > 1.0 - 1e-5,
+                        "{:?} elongated along {:?} has its long side along {}", direction, elongation, long_side);
+                }
+            }
+        }
+    }
+
+    fn cube(min: IVec3) -> block::Block {
+        block::Block::new(block::Direction::XP, min, min + IVec3::ONE)
+    }
+
+    #[test]
+    fn valid_level_has_no_errors() {
+        assert_eq!(Level(vec![cube(IVec3::ZERO), cube(IVec3::X)]).validate(), Vec::new());
+    }
+
+    #[test]
+    fn validate_reports_each_problem() {
+        assert_eq!(Level(Vec::new()).validate(), vec![LevelError::Empty]);
+        let flat = block::Block::new(block::Direction::XP, IVec3::ZERO, IVec3::new(1, 0, 1));
+        assert_eq!(Level(vec![flat]).validate(), vec![LevelError::NoExtent { index: 0, min: flat.min, max: flat.max }]);
+        let square = block::Block::new(block::Direction::XP, IVec3::ZERO, IVec3::new(2, 2, 1));
+        assert_eq!(Level(vec![square]).validate(), vec![LevelError::UnsupportedShape { index: 0, size: square.get_isize() }]);
+        let too_long = block::Block::new(block::Direction::XP, IVec3::ZERO, IVec3::new(block::Block::MAX_LENGTH + 1, 1, 1));
+        assert_eq!(Level(vec![too_long]).validate(), vec![LevelError::UnsupportedShape { index: 0, size: too_long.get_isize() }]);
+        let far = cube(IVec3::splat(Level::MAX_COORD));
+        assert_eq!(Level(vec![far]).validate(), vec![LevelError::OutOfBounds { index: 0, min: far.min, max: far.max }]);
+        assert_eq!(Level(vec![cube(IVec3::ZERO), cube(IVec3::X), cube(IVec3::ZERO)]).validate(), vec![LevelError::Overlap { first: 0, second: 2 }]);
+        let stuck = block::Block { step: 0, ..cube(IVec3::ZERO) };
+        assert_eq!(Level(vec![stuck]).validate(), vec![LevelError::BadStep { index: 0, step: 0 }]);
+    }
+
+    #[test]
+    fn flyaway_target_clears_blocks_still_leaving() {
+        let bounds = LevelBounds(IVec3::ZERO, IVec3::splat(4));
+        let leaving = cube(IVec3::ZERO);
+        // still flying out on the same line, right where the bound plus the margin would end
+        let ahead = block::Block::new(block::Direction::XP, IVec3::new(5, 0, 0), IVec3::new(7, 1, 1));
+        let target = get_flyaway_block_position(&leaving, &bounds, &[leaving, ahead]);
+        assert!(!target.overlaps(&ahead));
+        assert!(target.min.x >= ahead.max.x + FLYAWAY_MARGIN);
+        let alone = get_flyaway_block_position(&leaving, &bounds, &[leaving]);
+        assert_eq!(alone.min.x, 4 + FLYAWAY_MARGIN);
+    }
+
+    // the animation, win, hint and stuck systems wired up as in main, without a window. Spawn the
+    // level's blocks first, then call enter_level
+    fn headless_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, bevy::state::app::StatesPlugin))
+            .init_state::<Interface>()
+            .add_sub_state::<LevelLoadingState>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<keybindings::KeyBindings>()
+            .init_resource::<AnimationInProgress>()
+            .init_resource::<FreeBlocks>()
+            .init_resource::<LevelTimer>()
+            .insert_resource(LevelBounds(IVec3::ZERO, IVec3::splat(4)))
+            .add_systems(Update, (
+                (update_animation_in_progress, update_free_blocks),
+                (
+                    finish_level_if_done.run_if(not(animation_in_progress)),
+                    show_hint.run_if(not(animation_in_progress)),
+                    detect_stuck,
+                ),
+            ).chain().run_if(in_state(LevelLoadingState::Level)));
+        app
+    }
+
+    fn enter_level(app: &mut App) {
+        app.world_mut().resource_mut::<NextState<Interface>>().set(Interface::Gameplay);
+        app.update();
+        app.world_mut().resource_mut::<NextState<LevelLoadingState>>().set(LevelLoadingState::Level);
+        app.update();
+    }
+
+    fn level_state(app: &App) -> LevelLoadingState {
+        *app.world().resource::<State<LevelLoadingState>>().get()
+    }
+
+    fn flying(min: IVec3) -> (block::Block, MoveDest) {
+        (cube(min), MoveDest::new(min.as_vec3(), min.as_vec3() + Vec3::X * 8.0, true))
+    }
+
+    #[test]
+    fn win_waits_for_animations() {
+        let mut app = headless_app();
+        // whatever is still animating holds the win back, even without a Block on it
+        let moving = app.world_mut().spawn(MoveDest::new(Vec3::ZERO, Vec3::X, true)).id();
+        enter_level(&mut app);
+        app.update();
+        assert!(app.world().resource::<AnimationInProgress>().0);
+        assert_eq!(level_state(&app), LevelLoadingState::Level);
+        app.world_mut().despawn(moving);
+        app.update();
+        app.update();
+        assert_eq!(level_state(&app), LevelLoadingState::Cleared);
+    }
+
+    #[test]
+    fn hint_waits_for_animations() {
+        let mut app = headless_app();
+        let settled = app.world_mut().spawn(cube(IVec3::ZERO)).id();
+        let leaving = app.world_mut().spawn(flying(IVec3::new(0, 2, 0))).id();
+        app.world_mut().resource_mut::<ButtonInput<KeyCode>>().press(KeyCode::KeyH);
+        enter_level(&mut app);
+        app.update();
+        assert!(app.world().get::<HintPulse>(settled).is_none());
+        app.world_mut().despawn(leaving);
+        app.update();
+        assert!(app.world().get::<HintPulse>(settled).is_some());
+    }
+
+    #[test]
+    fn stuck_waits_for_animations() {
+        let mut app = headless_app();
+        // a facing pair, stuck for good once the third block is gone
+        app.world_mut().spawn(block::Block::new(block::Direction::XP, IVec3::ZERO, IVec3::ONE));
+        app.world_mut().spawn(block::Block::new(block::Direction::XN, IVec3::X, IVec3::new(2, 1, 1)));
+        let leaving = app.world_mut().spawn(flying(IVec3::new(0, 2, 0))).id();
+        enter_level(&mut app);
+        app.update();
+        let mut banners = app.world_mut().query_filtered::<(), With<StuckBanner>>();
+        assert_eq!(banners.iter(app.world()).count(), 0);
+        app.world_mut().despawn(leaving);
+        app.update();
+        assert_eq!(banners.iter(app.world()).count(), 1);
+    }
+
+    #[test]
+    fn level_with_a_block_flying_off_finishes_once_it_is_gone() {
+        let mut app = headless_app();
+        let leaving = app.world_mut().spawn(flying(IVec3::ZERO)).id();
+        enter_level(&mut app);
+        for _ in 0..3 {
+            app.update();
+            assert_eq!(level_state(&app), LevelLoadingState::Level);
+        }
+        app.world_mut().despawn(leaving);
+        app.update();
+        app.update();
+        assert_eq!(level_state(&app), LevelLoadingState::Cleared);
+    }
+
+    #[test]
+    fn finish_checks_for_moves_itself() {
+        let mut app = headless_app();
+        // a block whose Block is already gone
+        app.world_mut().spawn(MoveDest::new(Vec3::ZERO, Vec3::X, true));
+        enter_level(&mut app);
+        // with no run condition in front of the system
+        app.world_mut().run_system_once(finish_level_if_done).unwrap();
+        assert!(matches!(*app.world().resource::<NextState<LevelLoadingState>>(), NextState::Unchanged));
+    }
+
+    #[test]
+    fn long_frame_lands_on_dest() {
+        let mut move_dest = MoveDest::new(Vec3::ZERO, Vec3::new(0.0, 0.0, -3.0), false);
+        // a frame many times the length of the whole slide
+        move_dest.elapsed = move_dest.duration * 50.0;
+        assert_eq!(slide_translation(&move_dest), move_dest.dest);
+    }
+
+    #[test]
+    fn slide_never_passes_dest() {
+        let start = Vec3::new(1.0, 2.0, 0.0);
+        let mut move_dest = MoveDest::new(start, start + Vec3::X * 5.0, false);
+        let length = start.distance(move_dest.dest);
+        let mut last = 0.0;
+        for step in 0..=40 {
+            move_dest.elapsed = move_dest.duration * step as f32 / 32.0;
+            let position = slide_translation(&move_dest);
+            let along = (position - start).dot(Vec3::X);
+            assert!(along <= length, "step {}: {} past {}", step, along, length);
+            assert!(along >= last);
+            last = along;
+        }
+        assert_eq!(last, length);
+    }
 }
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::block::{Axis, Block, Direction};
+use crate::{LevelBounds, direction_color};
+
+// the axis the minimap looks down, from its positive end
+#[derive(Resource)]
+pub struct MinimapAxis(pub Axis);
+
+impl Default for MinimapAxis {
+    fn default() -> Self {
+        Self(Axis::Y)
+    }
+}
+
+const CELL_SIZE: f32 = 18.0;
+
+fn to_color32(color: Color) -> egui::Color32 {
+    let [r, g, b, _] = color.to_srgba().to_u8_array();
+    egui::Color32::from_rgb(r, g, b)
+}
+
+// how a direction looks on the map: along the view axis it points at or away from the viewer
+fn direction_glyph(direction: &Direction, view: Axis, [u, v]: [Axis; 2]) -> &'static str {
+    match (direction.axis, direction.positive) {
+        (a, true) if a == view => "•",
+        (a, false) if a == view => "×",
+        (a, true) if a == u => "→",
+        (a, false) if a == u => "←",
+        (a, true) if a == v => "↑",
+        _ => "↓",
+    }
+}
+
+pub fn draw_minimap(
+    mut contexts: EguiContexts,
+    mut view: ResMut<MinimapAxis>,
+    level_bounds: Res<LevelBounds>,
+    blocks: Query<&Block>,
+) -> Result {
+    let axis = view.0;
+    let plane = axis.remaining_two();
+    let LevelBounds(lower, upper) = *level_bounds;
+    let (u_range, v_range) = (
+        plane[0].ivec3_component(lower)..plane[0].ivec3_component(upper),
+        plane[1].ivec3_component(lower)..plane[1].ivec3_component(upper),
+    );
+    egui::Window::new("Map")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.horizontal(|ui| {
+                for (a, label) in [(Axis::X, "X"), (Axis::Y, "Y"), (Axis::Z, "Z")] {
+                    ui.selectable_value(&mut view.0, a, label);
+                }
+            });
+            let size = egui::vec2(u_range.len() as f32, v_range.len() as f32) * CELL_SIZE;
+            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(30));
+            for u in u_range.clone() {
+                for v in v_range.clone() {
+                    // the block seen first when looking down the axis from its positive end
+                    let top = blocks.iter()
+                        .filter(|b| {
+                            (plane[0].ivec3_component(b.min)..plane[0].ivec3_component(b.max)).contains(&u)
+                                && (plane[1].ivec3_component(b.min)..plane[1].ivec3_component(b.max)).contains(&v)
+                        })
+                        .max_by_key(|b| axis.ivec3_component(b.max));
+                    let Some(block) = top else { continue };
+                    let min = rect.left_bottom() + egui::vec2(
+                        (u - u_range.start) as f32 * CELL_SIZE,
+                        -((v - v_range.start + 1) as f32 * CELL_SIZE),
+                    );
+                    let cell = egui::Rect::from_min_size(min, egui::Vec2::splat(CELL_SIZE)).shrink(1.0);
+                    painter.rect_filled(cell, 2.0, to_color32(direction_color(&block.direction)));
+                    painter.text(
+                        cell.center(),
+                        egui::Align2::CENTER_CENTER,
+                        direction_glyph(&block.direction, axis, plane),
+                        egui::FontId::proportional(CELL_SIZE * 0.7),
+                        egui::Color32::WHITE,
+                    );
+                }
+            }
+        });
+    Ok(())
+}
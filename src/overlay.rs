@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use crate::block::Block;
+use crate::{LevelBounds, LevelCenter, direction_color};
+use crate::keybindings::{Action, KeyBindings};
+
+#[derive(Resource)]
+pub struct ShowBounds(pub bool);
+
+impl Default for ShowBounds {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ShowDirectionGizmos(pub bool);
+
+// generator debugging: the next generated level keeps the blocks remove_locked would take
+// out, and shows them in LOCKED_COLOR
+#[derive(Resource, Default)]
+pub struct ShowLockedBlocks(pub bool);
+
+// the blocks of the current level that remove_locked would have removed
+#[derive(Resource, Default)]
+pub struct LockedBlocks(pub Vec<Block>);
+
+pub const LOCKED_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+
+pub fn toggle_bounds(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut show_bounds: ResMut<ShowBounds>,
+) {
+    if bindings.just_pressed(&keys, Action::ToggleBounds) {
+        show_bounds.0 = !show_bounds.0;
+    }
+}
+
+// drawn every frame from LevelBounds, so it follows the level if its dimensions change.
+// Block translations are in grid units (only the models are scaled), so the box needs no scaling
+pub fn draw_level_bounds(
+    mut gizmos: Gizmos,
+    show_bounds: Res<ShowBounds>,
+    level_bounds: Res<LevelBounds>,
+    level_center: Res<LevelCenter>,
+) {
+    if !show_bounds.0 {
+        return;
+    }
+    let LevelBounds(lower, upper) = *level_bounds;
+    let (lower, upper) = (lower.as_vec3(), upper.as_vec3());
+    gizmos.cuboid(
+        Transform::from_translation(lower.midpoint(upper) - level_center.0).with_scale(upper - lower),
+        Color::srgba(1.0, 1.0, 1.0, 0.4),
+    );
+}
+
+#[cfg(feature = "dev")]
+pub fn toggle_locked_blocks(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut show_locked: ResMut<ShowLockedBlocks>,
+) {
+    if keys.just_pressed(KeyCode::F9) {
+        show_locked.0 = !show_locked.0;
+        info!("locked blocks are {} from the next generated level", if show_locked.0 { "kept" } else { "removed" });
+    }
+}
+
+pub fn toggle_direction_gizmos(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut show_gizmos: ResMut<ShowDirectionGizmos>,
+) {
+    if bindings.just_pressed(&keys, Action::ToggleDirections) {
+        show_gizmos.0 = !show_gizmos.0;
+    }
+}
+
+// arrows start at the block's center and poke out of its exit face, following the
+// block's transform so they stay attached while it slides. A faint line continues to the
+// edge of the level to show how far the block has to go
+pub fn draw_direction_gizmos(
+    mut gizmos: Gizmos,
+    show_gizmos: Res<ShowDirectionGizmos>,
+    level_bounds: Res<LevelBounds>,
+    blocks: Query<(&Block, &Transform)>,
+) {
+    if !show_gizmos.0 {
+        return;
+    }
+    let LevelBounds(lower, upper) = *level_bounds;
+    for (block, transform) in blocks.iter() {
+        let dir = block.direction.unit_vector();
+        let half_extent = (block.max - block.min).as_vec3().dot(dir.abs()) / 2.0;
+        let start = transform.translation;
+        let color = direction_color(&block.direction);
+        gizmos.arrow(start, start + dir * (half_extent + 0.5), color);
+        let face = start + dir * half_extent;
+        let distance = block.distance_to_exit(lower, upper).max(0) as f32;
+        gizmos.line(face, face + dir * distance, color.with_alpha(0.3));
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::{BlockSceneMarker, MoveDest};
+
+// a "+1" that rises from where a block left the level and fades out
+#[derive(Component)]
+pub struct Popup {
+    // where the block was when it set off, in world space
+    anchor: Vec3,
+    start: f32,
+    lifetime: f32,
+}
+
+impl Popup {
+    const LIFETIME: f32 = 0.6;
+    // how far it rises over its lifetime, in pixels
+    const RISE: f32 = 40.0;
+}
+
+pub fn spawn_exit_popups(
+    mut commands: Commands,
+    started: Query<&MoveDest, Added<MoveDest>>,
+    time: Res<Time>,
+) {
+    for move_dest in started.iter().filter(|m| m.should_despawn) {
+        commands.spawn((
+            Text::new("+1"),
+            TextFont {
+                font_size: 28.0,
+                ..default()
+            },
+            TextColor(Color::srgb(1.0, 0.85, 0.1)),
+            TextShadow::default(),
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            // placed by animate_popups before it's shown
+            Visibility::Hidden,
+            Pickable::IGNORE,
+            Popup { anchor: move_dest.start, start: time.elapsed_secs(), lifetime: Popup::LIFETIME },
+            BlockSceneMarker,
+        ));
+    }
+}
+
+// UI text follows the anchor on screen, so it faces the camera however the view is turned
+pub fn animate_popups(
+    mut commands: Commands,
+    mut popups: Query<(Entity, &Popup, &mut Node, &mut TextColor, &mut Visibility)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    time: Res<Time>,
+) {
+    let Ok((camera, camera_tr)) = camera_query.single() else { return };
+    for (entity, popup, mut node, mut color, mut visibility) in popups.iter_mut() {
+        let t = (time.elapsed_secs() - popup.start) / popup.lifetime;
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let Ok(position) = camera.world_to_viewport(camera_tr, popup.anchor) else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        node.left = Val::Px(position.x);
+        node.top = Val::Px(position.y - Popup::RISE * EaseFunction::CubicOut.sample_clamped(t));
+        color.0.set_alpha(1.0 - t.powi(2));
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::block::Block;
+use crate::{
+    Animating, BlockActivated, BlockModels, ClickableBlock, GotStuck, Level, LevelEntityFilter,
+    LevelTimer, MoveCount, draw_blocks,
+};
+use crate::achievements::UsedUndo;
+use crate::keybindings::{Action, KeyBindings};
+
+// one click: which block of the initial level it was, and where it went from and to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplayEvent {
+    // seconds on the level timer
+    pub time: f32,
+    // index into Replay::initial
+    pub entity_index: usize,
+    pub before: Block,
+    pub after: Block,
+}
+
+// every click of the current attempt, reset whenever the level is drawn
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub initial: Vec<Block>,
+    pub events: Vec<ReplayEvent>,
+    // where each block of `initial` is now, including pushed ones, so clicks can be told
+    // apart by index even after blocks were despawned and respawned by undo
+    #[serde(skip)]
+    positions: Vec<Block>,
+}
+
+impl Replay {
+    pub fn new(initial: Vec<Block>) -> Self {
+        Self { positions: initial.clone(), initial, events: Vec::new() }
+    }
+
+    // `moves` are all the (before, after) pairs caused by clicking the block at `clicked`
+    pub fn record(self: &mut Self, time: f32, clicked: Block, moves: &[(Block, Block)]) {
+        let Some(entity_index) = self.positions.iter().position(|b| *b == clicked) else { return };
+        let after = moves.iter().find(|(before, _)| *before == clicked).map_or(clicked, |m| m.1);
+        self.move_positions(moves.iter().copied());
+        self.events.push(ReplayEvent { time, entity_index, before: clicked, after });
+    }
+
+    // undo takes the last click out of the replay, as if it never happened
+    pub fn undo(self: &mut Self, moves: &[(Block, Block)]) {
+        self.move_positions(moves.iter().map(|(before, after)| (*after, *before)));
+        self.events.pop();
+    }
+
+    // keeps the replay in step with a board that was rotated or mirrored
+    pub fn transform(self: &mut Self, transform: impl Fn(&Block) -> Block) {
+        for block in self.initial.iter_mut().chain(self.positions.iter_mut()) {
+            *block = transform(block);
+        }
+        for event in self.events.iter_mut() {
+            (event.before, event.after) = (transform(&event.before), transform(&event.after));
+        }
+    }
+
+    fn move_positions(self: &mut Self, moves: impl Iterator<Item=(Block, Block)>) {
+        for (from, to) in moves {
+            if let Some(position) = self.positions.iter_mut().find(|b| **b == from) {
+                *position = to;
+            }
+        }
+    }
+}
+
+// present while a replay is played back
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    events: Vec<ReplayEvent>,
+    next: usize,
+}
+
+// P starts the current attempt over and plays its clicks back, Shift+P saves it instead
+#[allow(clippy::too_many_arguments)]
+pub fn start_playback(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    replay: Res<Replay>,
+    level_entities: Query<Entity, LevelEntityFilter>,
+    models: Res<BlockModels>,
+    mut move_count: ResMut<MoveCount>,
+    mut timer: ResMut<LevelTimer>,
+    time: Res<Time>,
+) {
+    if !bindings.just_pressed(&keys, Action::Replay) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        match write_replay_file(&replay) {
+            Ok(path) => info!("saved replay to {}", path.display()),
+            Err(err) => error!("failed to save replay: {}", err),
+        }
+        return;
+    }
+    if replay.events.is_empty() {
+        return;
+    }
+    level_entities.iter().for_each(|e| commands.entity(e).despawn());
+    commands.insert_resource(GotStuck::default());
+    commands.insert_resource(UsedUndo::default());
+    commands.insert_resource(ReplayPlayback { events: replay.events.clone(), next: 0 });
+    draw_blocks(commands, &Level(replay.initial.clone()), &models, time.elapsed_secs());
+    *move_count = MoveCount::default();
+    *timer = LevelTimer { elapsed: 0.0, running: true };
+}
+
+// clicks go through BlockActivated like the player's own, so they animate the same way. A click
+// whose block is still moving waits for it, so playback keeps up at any game speed
+pub fn drive_playback(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    timer: Res<LevelTimer>,
+    clickable: Query<(Entity, &Block), ClickableBlock>,
+    animating: Query<(), Animating>,
+    mut activated: EventWriter<BlockActivated>,
+) {
+    let Some(event) = playback.events.get(playback.next) else {
+        commands.remove_resource::<ReplayPlayback>();
+        return;
+    };
+    if timer.elapsed < event.time {
+        return;
+    }
+    match clickable.iter().find(|(_, b)| **b == event.before) {
+        Some((entity, _)) => {
+            activated.write(BlockActivated(entity));
+            playback.next += 1;
+        },
+        None if animating.is_empty() => {
+            warn!("replay stopped: block {} is not at {}..{}", event.entity_index, event.before.min, event.before.max);
+            commands.remove_resource::<ReplayPlayback>();
+        },
+        None => (),
+    }
+}
+
+fn write_replay_file(replay: &Replay) -> std::io::Result<std::path::PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let dir = std::path::Path::new("replays");
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("replay_{}.json", timestamp));
+    let json = serde_json::to_string_pretty(replay).map_err(std::io::Error::other)?;
+    std::fs::write(&path, json)?;
+    Ok(path)
+}
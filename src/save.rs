@@ -0,0 +1,83 @@
+use bevy::log::{error, warn};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use crate::achievements::Achievement;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub current_level: u8,
+    #[serde(default = "default_max_unlocked")]
+    pub max_unlocked: u8,
+    #[serde(default)]
+    pub best_times: HashMap<u8, f32>,
+    #[serde(default)]
+    pub total_score: u32,
+    // best daily challenge time, keyed by the YYYYMMDD date it was played on
+    #[serde(default)]
+    pub daily_bests: HashMap<u32, f32>,
+    #[serde(default)]
+    pub achievements: HashSet<Achievement>,
+}
+
+fn default_max_unlocked() -> u8 {
+    1
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        Self {
+            current_level: 1,
+            max_unlocked: 1,
+            best_times: HashMap::new(),
+            total_score: 0,
+            daily_bests: HashMap::new(),
+            achievements: HashSet::new(),
+        }
+    }
+}
+
+pub fn config_path(file_name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("clear-cube").join(file_name))
+}
+
+pub fn save_path() -> Option<PathBuf> {
+    config_path("save.json")
+}
+
+// a missing file starts fresh, a corrupt one is reported and replaced on the next save
+pub fn read_json<T: DeserializeOwned + Default>(path: Option<PathBuf>) -> T {
+    let Some(path) = path else { return T::default() };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return T::default(),
+    };
+    match serde_json::from_str(&contents) {
+        Ok(data) => data,
+        Err(err) => {
+            warn!("ignoring corrupt file {}: {}", path.display(), err);
+            T::default()
+        },
+    }
+}
+
+pub fn write_json<T: Serialize>(path: Option<PathBuf>, data: &T) {
+    let Some(path) = path else { return };
+    let written = path.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            let json = serde_json::to_string_pretty(data).map_err(std::io::Error::other)?;
+            std::fs::write(&path, json)
+        });
+    if let Err(err) = written {
+        error!("failed to write {}: {}", path.display(), err);
+    }
+}
+
+pub fn load() -> SaveData {
+    read_json(save_path())
+}
+
+pub fn store(data: &SaveData) {
+    write_json(save_path(), data)
+}
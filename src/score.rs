@@ -0,0 +1,46 @@
+// every level starts with a budget that grows with its size; extra moves and time eat into it
+const BASE_POINTS: u32 = 1000;
+const POINTS_PER_OPTIMAL_MOVE: u32 = 100;
+const EXTRA_MOVE_PENALTY: u32 = 50;
+const PENALTY_PER_SECOND: u32 = 5;
+pub const NOT_STUCK_BONUS: u32 = 250;
+
+pub fn level_score(moves: u32, secs: f32, optimal_moves: u32) -> u32 {
+    let budget = POINTS_PER_OPTIMAL_MOVE.saturating_mul(optimal_moves).saturating_add(BASE_POINTS);
+    // the float cast saturates, and so does everything after it
+    let penalty = EXTRA_MOVE_PENALTY.saturating_mul(moves.saturating_sub(optimal_moves))
+        .saturating_add(PENALTY_PER_SECOND.saturating_mul(secs.max(0.0) as u32));
+    budget.saturating_sub(penalty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optimal_instant_clear_scores_the_budget() {
+        assert_eq!(level_score(7, 0.0, 7), BASE_POINTS + 100 * 7);
+        assert_eq!(level_score(0, 0.0, 0), BASE_POINTS);
+    }
+
+    #[test]
+    fn penalties() {
+        let best = level_score(7, 0.0, 7);
+        assert_eq!(level_score(8, 0.0, 7), best - EXTRA_MOVE_PENALTY);
+        assert_eq!(level_score(10, 0.0, 7), best - 3 * EXTRA_MOVE_PENALTY);
+        assert_eq!(level_score(7, 1.0, 7), best - PENALTY_PER_SECOND);
+        // partial seconds don't count
+        assert_eq!(level_score(7, 12.9, 7), best - 12 * PENALTY_PER_SECOND);
+        assert_eq!(level_score(9, 4.0, 7), best - 2 * EXTRA_MOVE_PENALTY - 4 * PENALTY_PER_SECOND);
+        // fewer moves than the solver found earns nothing extra
+        assert_eq!(level_score(5, 0.0, 7), best);
+    }
+
+    #[test]
+    fn slow_or_long_clears_bottom_out_at_zero() {
+        assert_eq!(level_score(7, 1e6, 7), 0);
+        assert_eq!(level_score(7, f32::INFINITY, 7), 0);
+        assert_eq!(level_score(u32::MAX, 0.0, 7), 0);
+        assert_eq!(level_score(u32::MAX, f32::MAX, 0), 0);
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::{Interface, LevelSeed};
+
+// sizes that can be asked for along with a seed
+pub const MIN_SIDE_LEN: u8 = 2;
+pub const MAX_SIDE_LEN: u8 = 8;
+
+// present while playing a level typed in by seed. Like practice it doesn't count towards
+// progress, since anyone can pick any seed
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PlaySeed {
+    pub seed: u64,
+    pub side_len: u8,
+}
+
+// what was typed in, kept between visits to the screen
+#[derive(Resource)]
+pub struct SeedForm {
+    text: String,
+    side_len: u8,
+    error: Option<String>,
+}
+
+impl Default for SeedForm {
+    fn default() -> Self {
+        Self { text: String::new(), side_len: 4, error: None }
+    }
+}
+
+#[derive(Component)]
+pub struct SeedEntryMarker;
+
+pub fn setup_seed_entry(mut commands: Commands) {
+    commands.spawn((Camera2d, bevy_egui::PrimaryEguiContext, SeedEntryMarker));
+}
+
+pub fn despawn_seed_entry(
+    mut commands: Commands,
+    query: Query<Entity, With<SeedEntryMarker>>,
+) {
+    query.iter().for_each(|e| commands.entity(e).despawn());
+}
+
+// the seed of the last level played is shown too, so it can be copied and shared
+pub fn draw_seed_entry(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut form: ResMut<SeedForm>,
+    last_seed: Option<Res<LevelSeed>>,
+    mut istate: ResMut<NextState<Interface>>,
+) -> Result {
+    egui::Window::new("Play a seed")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .resizable(false)
+        .collapsible(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            let form = &mut *form;
+            ui.horizontal(|ui| {
+                ui.label("Seed");
+                ui.text_edit_singleline(&mut form.text);
+            });
+            ui.add(egui::Slider::new(&mut form.side_len, MIN_SIDE_LEN..=MAX_SIDE_LEN).text("Size"));
+            if let Some(error) = &form.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+            if let Some(last_seed) = &last_seed {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Last level: {}", last_seed.0));
+                    if ui.button("Copy").clicked() {
+                        ui.ctx().copy_text(last_seed.0.to_string());
+                    }
+                    if ui.button("Use").clicked() {
+                        form.text = last_seed.0.to_string();
+                    }
+                });
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Play").clicked() {
+                    match form.text.trim().parse::<u64>() {
+                        Ok(seed) => {
+                            form.error = None;
+                            commands.insert_resource(PlaySeed { seed, side_len: form.side_len });
+                            istate.set(Interface::Gameplay);
+                        },
+                        Err(err) => form.error = Some(format!("not a seed: {}", err)),
+                    }
+                }
+                if ui.button("Back").clicked() {
+                    istate.set(Interface::Menu);
+                }
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use crate::block::Block;
+use crate::{BlockActivated, ClickableBlock};
+
+// block picked with the keyboard, activated with Enter like a primary click
+#[derive(Resource, Default)]
+pub struct Selected(pub Option<Entity>);
+
+const MOVE_KEYS: [(KeyCode, KeyCode, Vec2); 4] = [
+    (KeyCode::ArrowUp, KeyCode::KeyW, Vec2::Y),
+    (KeyCode::ArrowDown, KeyCode::KeyS, Vec2::NEG_Y),
+    (KeyCode::ArrowLeft, KeyCode::KeyA, Vec2::NEG_X),
+    (KeyCode::ArrowRight, KeyCode::KeyD, Vec2::X),
+];
+
+// only blocks within 60 degrees of the pressed direction are considered
+const MIN_ALIGNMENT: f32 = 0.5;
+
+fn pressed_direction(keys: &ButtonInput<KeyCode>) -> Option<Vec2> {
+    MOVE_KEYS.iter()
+        .find(|(arrow, letter, _)| keys.just_pressed(*arrow) || keys.just_pressed(*letter))
+        .map(|(_, _, dir)| *dir)
+}
+
+// nearest block in the given screen direction, measured in the camera's view plane so
+// "up" and "right" follow the camera as it orbits
+pub fn block_in_direction(
+    from: Vec3,
+    screen_dir: Vec2,
+    camera: &GlobalTransform,
+    blocks: impl Iterator<Item=(Entity, Vec3)>,
+) -> Option<Entity> {
+    let forward = camera.forward().as_vec3();
+    let wanted = camera.right() * screen_dir.x + camera.up() * screen_dir.y;
+    blocks
+        .filter_map(|(entity, pos)| {
+            let offset = pos - from;
+            let in_view_plane = offset - forward * offset.dot(forward);
+            let distance = in_view_plane.length();
+            if distance < 0.1 {
+                return None;
+            }
+            let alignment = in_view_plane.dot(wanted) / distance;
+            (alignment >= MIN_ALIGNMENT).then_some((entity, distance * (2.0 - alignment)))
+        })
+        .min_by(|(_, s1), (_, s2)| s1.total_cmp(s2))
+        .map(|(entity, _)| entity)
+}
+
+// with nothing selected yet, the block closest to the camera is the natural starting point
+fn closest_to_camera(camera: &GlobalTransform, blocks: impl Iterator<Item=(Entity, Vec3)>) -> Option<Entity> {
+    blocks
+        .min_by(|(_, p1), (_, p2)| {
+            p1.distance_squared(camera.translation()).total_cmp(&p2.distance_squared(camera.translation()))
+        })
+        .map(|(entity, _)| entity)
+}
+
+pub fn step_selection(
+    selected: &mut Selected,
+    screen_dir: Vec2,
+    camera: &GlobalTransform,
+    blocks: &Query<(Entity, &Transform), With<Block>>,
+) {
+    let positions = blocks.iter().map(|(e, tr)| (e, tr.translation));
+    let current = selected.0.and_then(|e| blocks.get(e).ok());
+    let next = match current {
+        Some((_, tr)) => block_in_direction(tr.translation, screen_dir, camera, positions),
+        None => closest_to_camera(camera, positions),
+    };
+    if next.is_some() {
+        selected.0 = next;
+    }
+}
+
+pub fn move_selection(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut selected: ResMut<Selected>,
+    camera_query: Query<&GlobalTransform, With<PanOrbitCamera>>,
+    blocks: Query<(Entity, &Transform), With<Block>>,
+) {
+    let Some(screen_dir) = pressed_direction(&keys) else { return };
+    let Ok(camera) = camera_query.single() else { return };
+    step_selection(&mut selected, screen_dir, camera, &blocks);
+}
+
+// goes through BlockActivated so the selection behaves exactly like clicking
+pub fn activate_selection(
+    selected: &Selected,
+    clickable: &Query<(), ClickableBlock>,
+    activated: &mut EventWriter<BlockActivated>,
+) {
+    if let Some(entity) = selected.0.filter(|e| clickable.contains(*e)) {
+        activated.write(BlockActivated(entity));
+    }
+}
+
+pub fn activate_selected_on_enter(
+    keys: Res<ButtonInput<KeyCode>>,
+    selected: Res<Selected>,
+    clickable: Query<(), ClickableBlock>,
+    mut activated: EventWriter<BlockActivated>,
+) {
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter]) {
+        activate_selection(&selected, &clickable, &mut activated);
+    }
+}
+
+// once the selected block flies away, the selection moves to whatever is left closest to it
+pub fn keep_selection_valid(
+    mut selected: ResMut<Selected>,
+    blocks: Query<(Entity, &Transform), With<Block>>,
+    mut last_position: Local<Vec3>,
+) {
+    let Some(entity) = selected.0 else { return };
+    match blocks.get(entity) {
+        Ok((_, tr)) => *last_position = tr.translation,
+        Err(_) => {
+            selected.0 = blocks.iter()
+                .min_by(|(_, t1), (_, t2)| {
+                    t1.translation.distance_squared(*last_position)
+                        .total_cmp(&t2.translation.distance_squared(*last_position))
+                })
+                .map(|(e, _)| e);
+        },
+    }
+}
+
+pub fn draw_selection(
+    mut gizmos: Gizmos,
+    selected: Res<Selected>,
+    blocks: Query<(&Block, &Transform)>,
+) {
+    let Some((block, tr)) = selected.0.and_then(|e| blocks.get(e).ok()) else { return };
+    gizmos.cuboid(
+        Transform::from_translation(tr.translation).with_scale(block.get_size() * 1.05),
+        Color::srgb(1.0, 0.85, 0.1),
+    );
+}
@@ -0,0 +1,96 @@
+use bevy::prelude::Resource;
+use serde::{Serialize, Deserialize};
+use crate::save;
+
+// how a block leaves the level once it's past the edge
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlyawayStyle {
+    // keeps going the way it was clicked
+    #[default]
+    Straight,
+    // drops off the edge in an arc
+    Gravity,
+    // corkscrews around the way it's heading
+    Spin,
+}
+
+impl FlyawayStyle {
+    pub const ALL: [Self; 3] = [Self::Straight, Self::Gravity, Self::Spin];
+
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Straight => "Straight",
+            Self::Gravity => "Gravity",
+            Self::Spin => "Spin",
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub music_muted: bool,
+    // multiplies how fast blocks slide and fly away
+    pub game_speed: f32,
+    pub flyaway_style: FlyawayStyle,
+    pub show_bounds: bool,
+    pub show_direction_gizmos: bool,
+    // a floor under the level that the blocks cast shadows on
+    pub show_ground: bool,
+    // AmbientLight brightness, lifts the faces no light shines on
+    pub ambient_brightness: f32,
+    // multipliers on PanOrbitCamera's mouse and touch controls
+    pub orbit_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32,
+    // dragging up tilts the camera down instead of up
+    pub invert_y: bool,
+    // after a click the camera drifts a little towards the block that moved
+    pub auto_focus_on_move: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.5,
+            sfx_volume: 1.0,
+            music_muted: false,
+            game_speed: 1.0,
+            flyaway_style: FlyawayStyle::default(),
+            show_bounds: true,
+            show_direction_gizmos: false,
+            show_ground: true,
+            ambient_brightness: 400.0,
+            orbit_sensitivity: 1.0,
+            pan_sensitivity: 1.0,
+            zoom_sensitivity: 1.0,
+            invert_y: false,
+            auto_focus_on_move: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn effective_music_volume(self: &Self) -> f32 {
+        if self.music_muted { 0.0 } else { self.music_volume }
+    }
+}
+
+impl Settings {
+    pub const MIN_GAME_SPEED: f32 = 0.5;
+    pub const MAX_GAME_SPEED: f32 = 3.0;
+    pub const MIN_SENSITIVITY: f32 = 0.1;
+    pub const MAX_SENSITIVITY: f32 = 3.0;
+    pub const MIN_AMBIENT: f32 = 0.0;
+    pub const MAX_AMBIENT: f32 = 1500.0;
+}
+
+pub fn load() -> Settings {
+    save::read_json(save::config_path("settings.json"))
+}
+
+pub fn store(settings: &Settings) {
+    save::write_json(save::config_path("settings.json"), settings)
+}
@@ -0,0 +1,68 @@
+use glam::IVec3;
+use crate::block::{Block, BlockIndex};
+use crate::generation::{click, is_removable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Cleared,
+    // no block left on the board can fly away
+    Stuck { remaining: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayResult {
+    pub moves: u32,
+    pub outcome: Outcome,
+}
+
+// plays a level the way a player clicking only free blocks would, with the same click rules
+// as gameplay, so levels can be checked without running the app
+pub fn play_greedy(mut blocks: Vec<Block>, bound: (IVec3, IVec3)) -> PlayResult {
+    let mut moves = 0;
+    while !blocks.is_empty() {
+        let index = BlockIndex::new(blocks.iter().copied());
+        let Some(free) = blocks.iter().position(|b| is_removable(b, &index)) else {
+            return PlayResult { moves, outcome: Outcome::Stuck { remaining: blocks.len() } };
+        };
+        // a free block always flies away, so the click changes the board
+        let Some(next) = click(&blocks, free, bound) else {
+            return PlayResult { moves, outcome: Outcome::Stuck { remaining: blocks.len() } };
+        };
+        blocks = next;
+        moves += 1;
+    }
+    PlayResult { moves, outcome: Outcome::Cleared }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Direction;
+    use crate::generation::{Difficulty, generate_level_seeded};
+
+    #[test]
+    fn generated_levels_play_through() {
+        for difficulty in Difficulty::ALL {
+            for side_len in 2..=5 {
+                for seed in 0..20 {
+                    let blocks = generate_level_seeded(side_len, seed, &difficulty.params()).unwrap();
+                    let count = blocks.len() as u32;
+                    let bound = (IVec3::ZERO, IVec3::splat(side_len as i32));
+                    let result = play_greedy(blocks, bound);
+                    assert_eq!(result, PlayResult { moves: count, outcome: Outcome::Cleared }, "{:?} side {} seed {}", difficulty, side_len, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn facing_blocks_get_stuck() {
+        let blocks = vec![
+            Block::new(Direction::XP, IVec3::ZERO, IVec3::ONE),
+            Block::new(Direction::XN, IVec3::new(1, 0, 0), IVec3::new(2, 1, 1)),
+            Block::new(Direction::YP, IVec3::new(0, 1, 0), IVec3::new(1, 2, 1)),
+        ];
+        let result = play_greedy(blocks, (IVec3::ZERO, IVec3::new(2, 2, 1)));
+        assert_eq!(result, PlayResult { moves: 1, outcome: Outcome::Stuck { remaining: 2 } });
+    }
+}
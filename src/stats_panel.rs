@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::block::Direction;
+use crate::generation::{LevelStats, level_stats};
+use crate::InitialLevel;
+
+// generator tuning: logs the stats of every level as it's drawn and shows them in a panel
+#[derive(Resource, Default)]
+pub struct ShowLevelStats(pub bool);
+
+// stats of the level as it was drawn, only worked out while ShowLevelStats is on since that
+// runs the solver
+#[derive(Resource, Default)]
+pub struct CurrentLevelStats(Option<LevelStats>);
+
+pub fn toggle_level_stats(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut show_stats: ResMut<ShowLevelStats>,
+    mut current: ResMut<CurrentLevelStats>,
+) {
+    if keys.just_pressed(KeyCode::F10) {
+        show_stats.0 = !show_stats.0;
+        // worked out again for the level on screen
+        current.0 = None;
+    }
+}
+
+pub fn update_level_stats(
+    show_stats: Res<ShowLevelStats>,
+    initial_level: Res<InitialLevel>,
+    mut current: ResMut<CurrentLevelStats>,
+) {
+    if !show_stats.0 || (current.0.is_some() && !initial_level.is_changed()) {
+        return;
+    }
+    let stats = level_stats(&initial_level.0);
+    info!("level stats: {}", stats);
+    current.0 = Some(stats);
+}
+
+pub fn draw_level_stats(
+    mut contexts: EguiContexts,
+    show_stats: Res<ShowLevelStats>,
+    current: Res<CurrentLevelStats>,
+) -> Result {
+    let (true, Some(stats)) = (show_stats.0, current.0) else { return Ok(()) };
+    egui::Window::new("Level stats")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 90.0))
+        .resizable(false)
+        .collapsible(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Grid::new("level_stats").num_columns(2).show(ui, |ui| {
+                ui.label("Blocks");
+                ui.label(stats.blocks.to_string());
+                ui.end_row();
+                for (direction, count) in Direction::ALL.iter().zip(stats.per_direction) {
+                    ui.label(format!("{}{:?}", if direction.positive { "+" } else { "-" }, direction.axis));
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+                ui.label("Longest line");
+                ui.label(stats.longest_line.to_string());
+                ui.end_row();
+                ui.label("Solver depth");
+                ui.label(stats.solver_depth.map_or(String::from("unsolved"), |d| d.to_string()));
+                ui.end_row();
+            });
+        });
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use crate::{BlockSceneMarker, MoveCount};
+
+// the first levels are hand-made and teach the rules one at a time
+pub const TUTORIAL_LEVELS: u8 = 3;
+
+// always shipped with the game, so unlike other level files they aren't looked for first
+pub fn level_path(level: u8) -> Option<String> {
+    (1..=TUTORIAL_LEVELS).contains(&level).then(|| format!("tutorial/level{}.json", level))
+}
+
+// one prompt per move, the last one stays up for the rest of the level
+fn prompts(level: u8) -> &'static [&'static str] {
+    match level {
+        1 => &["Click the block to remove it"],
+        2 => &[
+            "Blocks only leave the way they point, and stop at anything in the way",
+            "Clear what's in the way first",
+            "Now the way is free",
+        ],
+        3 => &[
+            "Blocks can point in opposite directions, even on the same line",
+            "A block pointing at another one has to wait until that one is gone",
+            "Keep going until the cube is clear",
+        ],
+        _ => &[],
+    }
+}
+
+// present while a tutorial level is played
+#[derive(Resource)]
+pub struct TutorialPrompts(&'static [&'static str]);
+
+#[derive(Component)]
+pub struct TutorialText;
+
+pub fn start_tutorial(commands: &mut Commands, level: u8) {
+    let prompts = prompts(level);
+    let Some(first) = prompts.first() else { return };
+    commands.insert_resource(TutorialPrompts(prompts));
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Pickable::IGNORE,
+        BlockSceneMarker,
+        children![(
+            Text::new(*first),
+            TextFont {
+                font_size: 28.0,
+                ..default()
+            },
+            TextColor(Color::srgb(1.0, 0.95, 0.6)),
+            TextShadow::default(),
+            TutorialText,
+        )],
+    ));
+}
+
+// follows the move count, so undo and reset bring earlier prompts back
+pub fn update_tutorial_prompt(
+    prompts: Res<TutorialPrompts>,
+    move_count: Res<MoveCount>,
+    mut texts: Query<&mut Text, With<TutorialText>>,
+) {
+    let step = (move_count.0 as usize).min(prompts.0.len() - 1);
+    for mut text in texts.iter_mut() {
+        text.0 = String::from(prompts.0[step]);
+    }
+}